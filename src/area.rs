@@ -1,22 +1,32 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{Collider, RigidBody};
 
-use crate::common::{LEVEL_COLUMNS, LEVEL_ROWS, TILE_SIZE};
+use crate::level::LevelBounds;
 
 pub const WALL_THICKNESS: f32 = 10.0;
 
 #[derive(Debug, Component)]
 pub struct AreaWall;
 
-pub fn setup_wall(
+// 关卡尺寸变化时按新尺寸重建场地边界
+pub fn rebuild_wall(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    level_bounds: Res<LevelBounds>,
+    q_area_walls: Query<Entity, With<AreaWall>>,
 ) {
-    let left_wall = -LEVEL_COLUMNS as f32 / 2.0 * TILE_SIZE - WALL_THICKNESS / 2.0;
-    let right_wall = LEVEL_COLUMNS as f32 / 2.0 * TILE_SIZE + WALL_THICKNESS / 2.0;
-    let top_wall = LEVEL_ROWS as f32 / 2.0 * TILE_SIZE + WALL_THICKNESS / 2.0;
-    let bottom_wall = -LEVEL_ROWS as f32 / 2.0 * TILE_SIZE - WALL_THICKNESS / 2.0;
+    if !level_bounds.is_changed() {
+        return;
+    }
+    for entity in &q_area_walls {
+        commands.entity(entity).despawn();
+    }
+    let half_size = level_bounds.size() / 2.0;
+    let left_wall = -half_size.x - WALL_THICKNESS / 2.0;
+    let right_wall = half_size.x + WALL_THICKNESS / 2.0;
+    let top_wall = half_size.y + WALL_THICKNESS / 2.0;
+    let bottom_wall = -half_size.y - WALL_THICKNESS / 2.0;
     let arena_height = top_wall - bottom_wall;
     let arena_width = right_wall - left_wall;
     let wall_color = Color::srgb(0.8, 0.8, 0.8);
@@ -0,0 +1,405 @@
+use std::time::Duration;
+
+use bevy::audio::{Pitch, Volume};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::AppState;
+use crate::generator::GeneratedLevel;
+use crate::map::{CustomLevel, LevelPack};
+use crate::player::PlayerNo;
+use crate::settings::{OptionsMenu, Settings};
+
+// 暂停时音乐和音效的音量比例
+const DUCK_VOLUME: f32 = 0.3;
+
+// 引擎声暂时用固定频率的正弦波循环音占位，还没有录制的引擎音效
+const ENGINE_IDLE_FREQUENCY: f32 = 82.;
+const ENGINE_MOVING_FREQUENCY: f32 = 123.;
+const ENGINE_GAIN: f32 = 0.25;
+
+// 背景音乐暂时由正弦波音符表合成占位，还没有真正的循环音轨，一拍的时长
+const BEAT_SECS: f32 = 0.15;
+const MUSIC_GAIN: f32 = 0.4;
+
+// 音符：MIDI音高和拍数，音高为0表示休止
+type Note = (u8, f32);
+
+// 每关的背景音乐，按关卡序号循环使用
+#[rustfmt::skip]
+const STAGE_MUSIC: [&[Note]; 4] = [
+    &[
+        (60, 1.), (64, 1.), (67, 1.), (64, 1.), (65, 1.), (69, 1.), (67, 2.),
+        (64, 1.), (67, 1.), (72, 1.), (67, 1.), (65, 1.), (62, 1.), (60, 2.),
+    ],
+    &[
+        (57, 1.), (60, 1.), (64, 2.), (62, 1.), (60, 1.), (59, 2.),
+        (57, 1.), (59, 1.), (60, 1.), (62, 1.), (64, 2.), (0, 2.),
+    ],
+    &[
+        (62, 1.), (62, 1.), (69, 2.), (67, 1.), (65, 1.), (64, 2.),
+        (65, 1.), (67, 1.), (69, 1.), (65, 1.), (62, 2.), (0, 2.),
+    ],
+    &[
+        (55, 1.), (59, 1.), (62, 1.), (67, 1.), (66, 2.), (62, 2.),
+        (64, 1.), (62, 1.), (59, 1.), (57, 1.), (55, 2.), (0, 2.),
+    ],
+];
+
+// 通关胜利音乐，只播放一次
+#[rustfmt::skip]
+const VICTORY_JINGLE: &[Note] = &[
+    (72, 1.), (76, 1.), (79, 1.), (84, 3.), (0, 1.), (79, 1.), (84, 6.),
+];
+
+#[derive(Debug, Resource)]
+pub struct GameSounds {
+    pub start_menu: Handle<AudioSource>,
+    pub mode_switch: Handle<AudioSource>,
+    pub bullet_explosion: Handle<AudioSource>,
+    pub big_explosion: Handle<AudioSource>,
+    pub player_fire: Handle<AudioSource>,
+    pub game_over: Handle<AudioSource>,
+    pub game_pause: Handle<AudioSource>,
+    pub engine_idle: Handle<Pitch>,
+    pub engine_moving: Handle<Pitch>,
+}
+
+pub fn setup_game_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut pitches: ResMut<Assets<Pitch>>,
+) {
+    // 一秒内正好是整数个周期，循环时没有爆音
+    let engine_loop = Duration::from_secs(1);
+    commands.insert_resource(GameSounds {
+        start_menu: asset_server.load("sounds/start_menu.ogg"),
+        mode_switch: asset_server.load("sounds/mode_switch.ogg"),
+        bullet_explosion: asset_server.load("sounds/bullet_explosion.ogg"),
+        big_explosion: asset_server.load("sounds/big_explosion.ogg"),
+        player_fire: asset_server.load("sounds/player_fire.ogg"),
+        game_over: asset_server.load("sounds/game_over.ogg"),
+        game_pause: asset_server.load("sounds/game_pause.ogg"),
+        engine_idle: pitches.add(Pitch::new(ENGINE_IDLE_FREQUENCY, engine_loop)),
+        engine_moving: pitches.add(Pitch::new(ENGINE_MOVING_FREQUENCY, engine_loop)),
+    });
+}
+
+// 混音通道，每个通道音量独立设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum SoundChannel {
+    Music,
+    Sfx,
+    Ui,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Serialize, Deserialize)]
+pub enum Sound {
+    StartMenu,
+    ModeSwitch,
+    BulletExplosion,
+    BigExplosion,
+    PlayerFire,
+    GameOver,
+    GamePause,
+}
+
+impl Sound {
+    pub fn channel(self) -> SoundChannel {
+        match self {
+            Sound::StartMenu | Sound::GameOver => SoundChannel::Music,
+            Sound::BulletExplosion | Sound::BigExplosion | Sound::PlayerFire => SoundChannel::Sfx,
+            Sound::ModeSwitch | Sound::GamePause => SoundChannel::Ui,
+        }
+    }
+
+    // 同一种声音最多同时播放的数量，防止多个爆炸叠加导致爆音
+    pub fn max_voices(self) -> usize {
+        match self {
+            Sound::StartMenu | Sound::GameOver | Sound::GamePause => 1,
+            Sound::ModeSwitch => 2,
+            Sound::BigExplosion => 2,
+            Sound::BulletExplosion | Sound::PlayerFire => 3,
+        }
+    }
+
+    fn handle(self, game_sounds: &GameSounds) -> Handle<AudioSource> {
+        match self {
+            Sound::StartMenu => game_sounds.start_menu.clone(),
+            Sound::ModeSwitch => game_sounds.mode_switch.clone(),
+            Sound::BulletExplosion => game_sounds.bullet_explosion.clone(),
+            Sound::BigExplosion => game_sounds.big_explosion.clone(),
+            Sound::PlayerFire => game_sounds.player_fire.clone(),
+            Sound::GameOver => game_sounds.game_over.clone(),
+            Sound::GamePause => game_sounds.game_pause.clone(),
+        }
+    }
+}
+
+// 在混音器音量基础上的固定增益，合成音比采样音响
+#[derive(Debug, Component)]
+pub struct SoundGain(pub f32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum EngineSound {
+    Idle,
+    Moving,
+}
+
+#[derive(Component)]
+pub struct MusicNote;
+
+// 背景音乐播放进度
+#[derive(Debug, Resource, Default)]
+pub struct MusicPlayer {
+    notes: &'static [Note],
+    looping: bool,
+    next: usize,
+    timer: Timer,
+    paused: bool,
+}
+
+impl MusicPlayer {
+    fn play(&mut self, notes: &'static [Note], looping: bool) {
+        *self = MusicPlayer {
+            notes,
+            looping,
+            ..default()
+        };
+    }
+}
+
+// 播放声音统一通过事件发送给混音器
+#[derive(Debug, Clone, Copy, Event)]
+pub struct SoundEvent(pub Sound);
+
+#[derive(Debug, Resource, Default)]
+pub struct AudioMixer {
+    pub muted: bool,
+    pub ducked: bool,
+}
+
+impl AudioMixer {
+    pub fn volume(&self, settings: &Settings, channel: SoundChannel) -> f32 {
+        if self.muted {
+            return 0.;
+        }
+        let duck = if self.ducked && channel != SoundChannel::Ui {
+            DUCK_VOLUME
+        } else {
+            1.
+        };
+        settings.volume(channel) * duck
+    }
+}
+
+pub fn play_sounds(
+    mut commands: Commands,
+    mut sound_er: EventReader<SoundEvent>,
+    q_voices: Query<&Sound, With<AudioPlayer>>,
+    game_sounds: Res<GameSounds>,
+    settings: Res<Settings>,
+    audio_mixer: Res<AudioMixer>,
+) {
+    let mut started: Vec<Sound> = Vec::new();
+    for SoundEvent(sound) in sound_er.read() {
+        let voices = q_voices
+            .iter()
+            .chain(started.iter())
+            .filter(|voice| *voice == sound)
+            .count();
+        if voices >= sound.max_voices() {
+            continue;
+        }
+        started.push(*sound);
+        let channel = sound.channel();
+        commands.spawn((
+            AudioPlayer(sound.handle(&game_sounds)),
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::new(audio_mixer.volume(&settings, channel))),
+            channel,
+            *sound,
+        ));
+    }
+}
+
+pub fn toggle_mute(keyboard_input: Res<ButtonInput<KeyCode>>, mut audio_mixer: ResMut<AudioMixer>) {
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        audio_mixer.muted = !audio_mixer.muted;
+        info!("Audio muted: {}", audio_mixer.muted);
+    }
+}
+
+// 暂停和暂停中打开选项菜单时压低音乐和音效
+pub fn duck_while_paused(
+    app_state: Res<State<AppState>>,
+    options_menu: Res<OptionsMenu>,
+    mut audio_mixer: ResMut<AudioMixer>,
+) {
+    let ducked = match app_state.get() {
+        AppState::Paused => true,
+        AppState::Options => options_menu.return_state == AppState::Paused,
+        _ => false,
+    };
+    if audio_mixer.ducked != ducked {
+        audio_mixer.ducked = ducked;
+    }
+}
+
+// 设置或混音状态改变后调整正在播放的声音
+pub fn update_mixer_volume(
+    settings: Res<Settings>,
+    audio_mixer: Res<AudioMixer>,
+    q_sinks: Query<(&AudioSink, &SoundChannel, Option<&SoundGain>)>,
+) {
+    if !settings.is_changed() && !audio_mixer.is_changed() {
+        return;
+    }
+    for (sink, channel, gain) in &q_sinks {
+        let gain = gain.map_or(1., |gain| gain.0);
+        sink.set_volume(audio_mixer.volume(&settings, *channel) * gain);
+    }
+}
+
+// 根据玩家坦克是否在移动切换引擎声，没有玩家时停止
+pub fn update_engine_sound(
+    mut commands: Commands,
+    q_players: Query<&Velocity, With<PlayerNo>>,
+    q_engine: Query<(Entity, &EngineSound)>,
+    game_sounds: Res<GameSounds>,
+    settings: Res<Settings>,
+    audio_mixer: Res<AudioMixer>,
+) {
+    let engine = if q_players.is_empty() {
+        None
+    } else if q_players
+        .iter()
+        .any(|velocity| velocity.linvel != Vec2::ZERO)
+    {
+        Some(EngineSound::Moving)
+    } else {
+        Some(EngineSound::Idle)
+    };
+    if q_engine.iter().map(|(_, engine)| *engine).eq(engine) {
+        return;
+    }
+    for (entity, _) in &q_engine {
+        commands.entity(entity).despawn();
+    }
+    let Some(engine) = engine else {
+        return;
+    };
+    let handle = match engine {
+        EngineSound::Idle => game_sounds.engine_idle.clone(),
+        EngineSound::Moving => game_sounds.engine_moving.clone(),
+    };
+    let volume = audio_mixer.volume(&settings, SoundChannel::Sfx) * ENGINE_GAIN;
+    commands.spawn((
+        AudioPlayer(handle),
+        PlaybackSettings::LOOP.with_volume(Volume::new(volume)),
+        SoundChannel::Sfx,
+        SoundGain(ENGINE_GAIN),
+        engine,
+    ));
+}
+
+pub fn stop_engine_sound(mut commands: Commands, q_engine: Query<Entity, With<EngineSound>>) {
+    for entity in &q_engine {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn play_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut music_player: ResMut<MusicPlayer>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    settings: Res<Settings>,
+    audio_mixer: Res<AudioMixer>,
+) {
+    if music_player.paused || music_player.notes.is_empty() {
+        return;
+    }
+    if !music_player.timer.tick(time.delta()).finished() {
+        return;
+    }
+    if music_player.next == music_player.notes.len() {
+        if !music_player.looping {
+            *music_player = MusicPlayer::default();
+            return;
+        }
+        music_player.next = 0;
+    }
+    let (pitch, beats) = music_player.notes[music_player.next];
+    music_player.next += 1;
+    let secs = beats * BEAT_SECS;
+    music_player.timer = Timer::from_seconds(secs, TimerMode::Once);
+    if pitch == 0 {
+        return;
+    }
+    // 音符之间留一点间隔
+    let frequency = 440. * 2f32.powf((pitch as f32 - 69.) / 12.);
+    let note = pitches.add(Pitch::new(frequency, Duration::from_secs_f32(secs * 0.9)));
+    let volume = audio_mixer.volume(&settings, SoundChannel::Music) * MUSIC_GAIN;
+    commands.spawn((
+        AudioPlayer(note),
+        PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)),
+        SoundChannel::Music,
+        SoundGain(MUSIC_GAIN),
+        MusicNote,
+    ));
+}
+
+pub fn stop_music(
+    mut commands: Commands,
+    mut music_player: ResMut<MusicPlayer>,
+    q_notes: Query<Entity, With<MusicNote>>,
+) {
+    *music_player = MusicPlayer::default();
+    for entity in &q_notes {
+        commands.entity(entity).despawn();
+    }
+}
+
+// 以下系统在状态切换时播放对应的音乐
+pub fn play_start_menu_music(mut sound_ew: EventWriter<SoundEvent>) {
+    sound_ew.send(SoundEvent(Sound::StartMenu));
+}
+
+// 进入关卡时播放本关音乐，从暂停恢复时继续播放
+pub fn play_stage_music(
+    mut music_player: ResMut<MusicPlayer>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    generated_level: Res<GeneratedLevel>,
+    level_pack: Res<LevelPack>,
+) {
+    if music_player.paused {
+        music_player.paused = false;
+        return;
+    }
+    let stage = match (&custom_level.0, generated_level.0) {
+        (Some(_), Some(seed)) => seed as usize,
+        (Some(_), None) => level_pack.current,
+        (None, _) => match *level_selection {
+            LevelSelection::Indices(LevelIndices { level, .. }) => level,
+            _ => 0,
+        },
+    };
+    music_player.play(STAGE_MUSIC[stage % STAGE_MUSIC.len()], true);
+}
+
+pub fn pause_music(mut music_player: ResMut<MusicPlayer>, mut sound_ew: EventWriter<SoundEvent>) {
+    music_player.paused = true;
+    sound_ew.send(SoundEvent(Sound::GamePause));
+}
+
+pub fn play_victory_jingle(mut music_player: ResMut<MusicPlayer>) {
+    music_player.play(VICTORY_JINGLE, false);
+}
+
+pub fn play_game_over_music(mut sound_ew: EventWriter<SoundEvent>) {
+    sound_ew.send(SoundEvent(Sound::GameOver));
+}
@@ -0,0 +1,39 @@
+use battle_city::lint::{lint_level, load_lint_levels, LintSeverity};
+use std::process::ExitCode;
+
+// 关卡检查工具，用法: battle-city-lint [关卡文件...]，默认检查 assets/levels.ldtk
+fn main() -> ExitCode {
+    let mut paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        paths.push("assets/levels.ldtk".to_string());
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for path in &paths {
+        let levels = match load_lint_levels(path) {
+            Ok(levels) => levels,
+            Err(e) => {
+                eprintln!("{}: error: {}", path, e);
+                errors += 1;
+                continue;
+            }
+        };
+        for level in &levels {
+            for issue in lint_level(level) {
+                match issue.severity {
+                    LintSeverity::Error => errors += 1,
+                    LintSeverity::Warning => warnings += 1,
+                }
+                println!("{}:{}", path, issue);
+            }
+        }
+    }
+
+    println!("{} error(s), {} warning(s)", errors, warnings);
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
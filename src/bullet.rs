@@ -2,10 +2,14 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::area::*;
+use crate::audio::{Sound, SoundEvent};
 use crate::common::{self, Direction, *};
-use crate::enemy::Enemy;
+use crate::debug::GodMode;
+use crate::enemy::{Enemy, EnemyArmor};
+use crate::event_log::{GameEvent, HitTarget, Shooter};
 use crate::level::LevelItem;
-use crate::player::{PlayerLives, PlayerNo, Shield};
+use crate::player::{PlayerLives, PlayerNo, PlayerScores, Shield};
+use crate::sprites::SpriteAssets;
 
 pub const BULLET_SPEED: f32 = 300.0;
 
@@ -15,13 +19,17 @@ pub enum Bullet {
     Enemy,
 }
 
+// 玩家子弹的发射者，用于计分
+#[derive(Debug, Component)]
+pub struct BulletOwner(pub PlayerNo);
+
 #[derive(Debug, Component)]
 pub struct Explosion;
 
 #[derive(Debug, Event)]
 pub struct ExplosionEvent {
-    pos: Vec3,
-    explosion_type: ExplosionType,
+    pub pos: Vec3,
+    pub explosion_type: ExplosionType,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -34,27 +42,75 @@ pub enum ExplosionType {
 pub struct ExplosionAssets {
     pub big_explosion: Vec<Handle<Image>>,
     pub bullet_explosion: Vec<Handle<Image>>,
+    // 帧图片加载完成后拼成的图集，只生成一次
+    pub big_explosion_atlas: Option<(Handle<Image>, Handle<TextureAtlasLayout>)>,
+    pub bullet_explosion_atlas: Option<(Handle<Image>, Handle<TextureAtlasLayout>)>,
 }
 
 pub fn setup_explosion_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let mut big_explosion: Vec<Handle<Image>> = Vec::new();
-    big_explosion.push(asset_server.load("textures/big_explosion_1.png"));
-    big_explosion.push(asset_server.load("textures/big_explosion_2.png"));
-    big_explosion.push(asset_server.load("textures/big_explosion_3.png"));
-    big_explosion.push(asset_server.load("textures/big_explosion_4.png"));
-    big_explosion.push(asset_server.load("textures/big_explosion_5.png"));
+    let big_explosion: Vec<Handle<Image>> = vec![
+        asset_server.load("textures/big_explosion_1.png"),
+        asset_server.load("textures/big_explosion_2.png"),
+        asset_server.load("textures/big_explosion_3.png"),
+        asset_server.load("textures/big_explosion_4.png"),
+        asset_server.load("textures/big_explosion_5.png"),
+    ];
 
-    let mut bullet_explosion: Vec<Handle<Image>> = Vec::new();
-    bullet_explosion.push(asset_server.load("textures/bullet_explosion_1.png"));
-    bullet_explosion.push(asset_server.load("textures/bullet_explosion_2.png"));
-    bullet_explosion.push(asset_server.load("textures/bullet_explosion_3.png"));
+    let bullet_explosion: Vec<Handle<Image>> = vec![
+        asset_server.load("textures/bullet_explosion_1.png"),
+        asset_server.load("textures/bullet_explosion_2.png"),
+        asset_server.load("textures/bullet_explosion_3.png"),
+    ];
 
     commands.insert_resource(ExplosionAssets {
         big_explosion,
         bullet_explosion,
+        big_explosion_atlas: None,
+        bullet_explosion_atlas: None,
     });
 }
 
+pub fn build_explosion_atlases(
+    mut explosion_assets: ResMut<ExplosionAssets>,
+    mut textures: ResMut<Assets<Image>>,
+    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    if explosion_assets.big_explosion_atlas.is_none() {
+        explosion_assets.big_explosion_atlas = build_atlas(
+            &explosion_assets.big_explosion,
+            &mut textures,
+            &mut atlas_layouts,
+        );
+    }
+    if explosion_assets.bullet_explosion_atlas.is_none() {
+        explosion_assets.bullet_explosion_atlas = build_atlas(
+            &explosion_assets.bullet_explosion,
+            &mut textures,
+            &mut atlas_layouts,
+        );
+    }
+}
+
+// 所有帧都加载完成才生成图集
+fn build_atlas(
+    frames: &[Handle<Image>],
+    textures: &mut Assets<Image>,
+    atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> Option<(Handle<Image>, Handle<TextureAtlasLayout>)> {
+    let mut builder = TextureAtlasBuilder::default();
+    for handle in frames {
+        builder.add_texture(Some(handle.id()), textures.get(handle.id())?);
+    }
+    let (layout, _, image) = match builder.build() {
+        Ok(atlas) => atlas,
+        Err(e) => {
+            warn!("Failed to build explosion atlas: {}", e);
+            return None;
+        }
+    };
+    Some((textures.add(image), atlas_layouts.add(layout)))
+}
+
 // 炮弹移动
 pub fn move_bullet(
     mut q_bullet: Query<(&mut Transform, &common::Direction), With<Bullet>>,
@@ -80,17 +136,23 @@ pub fn move_bullet(
 
 pub fn handle_bullet_collision(
     mut commands: Commands,
-    q_bullets: Query<(Entity, &Bullet, &Transform)>,
+    q_bullets: Query<(Entity, &Bullet, &Transform, Option<&BulletOwner>)>,
     q_level_items: Query<(&LevelItem, &GlobalTransform, &mut Sprite)>,
     q_area_wall: Query<(), With<AreaWall>>,
-    q_players: Query<(&Transform, &Children), With<PlayerNo>>,
+    q_players: Query<(&Transform, &Children, &PlayerNo)>,
     q_shields: Query<Entity, With<Shield>>,
-    q_enemies: Query<&Transform, With<Enemy>>,
+    mut q_enemies: Query<(&Transform, &mut EnemyArmor), With<Enemy>>,
     mut collision_er: EventReader<CollisionEvent>,
     mut explosion_ew: EventWriter<ExplosionEvent>,
     mut home_dying_ew: EventWriter<HomeDyingEvent>,
+    mut game_event_ew: EventWriter<GameEvent>,
     player_lives: Res<PlayerLives>,
-    multiplayer_mode: Res<MultiplayerMode>,
+    mut player_scores: ResMut<PlayerScores>,
+    (multiplayer_mode, versus_mode, god_mode): (
+        Res<MultiplayerMode>,
+        Res<VersusMode>,
+        Res<GodMode>,
+    ),
     mut app_state: ResMut<NextState<AppState>>,
 ) {
     for event in collision_er.read() {
@@ -110,26 +172,24 @@ pub fn handle_bullet_collision(
                     *entity1
                 };
 
-                println!(
-                    "bullet: {:?}, collision entity1: {:?}, entity2: {:?}",
-                    bullet_entity, entity1, entity2
-                );
-
-                let (_, bullet, bullet_transform) = q_bullets.get(bullet_entity).unwrap();
+                let (_, bullet, bullet_transform, bullet_owner) =
+                    q_bullets.get(bullet_entity).unwrap();
+                let shooter = Shooter::of_bullet(bullet, bullet_owner);
+                let hit = |target| GameEvent::Hit {
+                    shooter,
+                    target,
+                    x: bullet_transform.translation.x,
+                    y: bullet_transform.translation.y,
+                };
 
-                info!("bullet hit something");
                 // 另一个物体
                 if q_level_items.contains(other_entity) {
-                    info!("Bullet hit level item");
                     let (level_item, level_item_transform, _) =
                         q_level_items.get(other_entity).unwrap();
-                    dbg!(level_item);
-                    // dbg!(bullet_transform);
-                    // dbg!(level_item_transform);
                     match level_item {
                         LevelItem::Home => {
                             // Game Over
-                            println!("Game over");
+                            game_event_ew.send(hit(HitTarget::Home));
                             commands.entity(bullet_entity).despawn();
                             explosion_ew.send(ExplosionEvent {
                                 pos: Vec3::new(
@@ -142,6 +202,12 @@ pub fn handle_bullet_collision(
                             home_dying_ew.send_default();
                         }
                         LevelItem::StoneWall => {
+                            game_event_ew.send(hit(HitTarget::StoneWall));
+                            game_event_ew.send(GameEvent::WallDestroyed {
+                                shooter,
+                                x: level_item_transform.translation().x,
+                                y: level_item_transform.translation().y,
+                            });
                             commands.entity(bullet_entity).despawn();
                             commands.entity(other_entity).despawn();
                             explosion_ew.send(ExplosionEvent {
@@ -154,6 +220,7 @@ pub fn handle_bullet_collision(
                             });
                         }
                         LevelItem::IronWall => {
+                            game_event_ew.send(hit(HitTarget::IronWall));
                             commands.entity(bullet_entity).despawn();
                             explosion_ew.send(ExplosionEvent {
                                 pos: Vec3::new(
@@ -169,7 +236,7 @@ pub fn handle_bullet_collision(
                 }
 
                 if q_area_wall.contains(other_entity) {
-                    info!("Bullet hit area wall");
+                    game_event_ew.send(hit(HitTarget::Boundary));
                     commands.entity(bullet_entity).despawn();
                     explosion_ew.send(ExplosionEvent {
                         pos: Vec3::new(
@@ -182,10 +249,33 @@ pub fn handle_bullet_collision(
                 }
 
                 if *bullet == Bullet::Player && q_enemies.contains(other_entity) {
-                    info!("Player bullet hit enemy");
-                    let enemy_transform = q_enemies.get(other_entity).unwrap();
+                    let (enemy_transform, mut enemy_armor) =
+                        q_enemies.get_mut(other_entity).unwrap();
+                    game_event_ew.send(hit(HitTarget::Enemy));
+                    if enemy_armor.0 > 1 {
+                        // 装甲未被击穿
+                        enemy_armor.0 -= 1;
+                        commands.entity(bullet_entity).despawn();
+                        explosion_ew.send(ExplosionEvent {
+                            pos: Vec3::new(
+                                bullet_transform.translation.x,
+                                bullet_transform.translation.y,
+                                bullet_transform.translation.z,
+                            ),
+                            explosion_type: ExplosionType::BulletExplosion,
+                        });
+                        continue;
+                    }
+                    game_event_ew.send(GameEvent::Kill {
+                        killer: shooter,
+                        x: enemy_transform.translation.x,
+                        y: enemy_transform.translation.y,
+                    });
                     commands.entity(bullet_entity).despawn();
                     commands.entity(other_entity).despawn();
+                    if let Some(bullet_owner) = bullet_owner {
+                        player_scores.add(bullet_owner.0, ENEMY_SCORE);
+                    }
                     explosion_ew.send(ExplosionEvent {
                         pos: Vec3::new(
                             enemy_transform.translation.x,
@@ -196,9 +286,17 @@ pub fn handle_bullet_collision(
                     });
                 }
 
-                if *bullet == Bullet::Enemy && q_players.contains(other_entity) {
-                    info!("Enemy bullet hit player");
-                    let (player_transform, player_children) = q_players.get(other_entity).unwrap();
+                // 对战模式下玩家子弹击中另一名玩家和敌人子弹一样处理
+                let hit_player = q_players
+                    .get(other_entity)
+                    .ok()
+                    .filter(|(_, _, player_no)| {
+                        *bullet == Bullet::Enemy
+                            || (versus_mode.0
+                                && bullet_owner.is_some_and(|owner| owner.0 .0 != player_no.0))
+                    });
+                if let Some((player_transform, player_children, player_no)) = hit_player {
+                    game_event_ew.send(hit(HitTarget::player(*player_no)));
                     let mut player_has_shield = false;
                     for child in player_children.iter() {
                         if q_shields.contains(*child) {
@@ -209,8 +307,7 @@ pub fn handle_bullet_collision(
 
                     commands.entity(bullet_entity).despawn();
 
-                    if player_has_shield {
-                        info!("Player has shield");
+                    if player_has_shield || god_mode.0 {
                         explosion_ew.send(ExplosionEvent {
                             pos: Vec3::new(
                                 player_transform.translation.x,
@@ -220,6 +317,17 @@ pub fn handle_bullet_collision(
                             explosion_type: ExplosionType::BulletExplosion,
                         });
                     } else {
+                        game_event_ew.send(GameEvent::PlayerDeath {
+                            player: player_no.0,
+                            killer: shooter,
+                            lives_left: if player_no.0 == 2 {
+                                player_lives.player2
+                            } else {
+                                player_lives.player1
+                            },
+                            x: player_transform.translation.x,
+                            y: player_transform.translation.y,
+                        });
                         commands.entity(other_entity).despawn_recursive();
                         explosion_ew.send(ExplosionEvent {
                             pos: Vec3::new(
@@ -246,120 +354,80 @@ pub fn handle_bullet_collision(
 
 pub fn spawn_bullet(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: &SpriteAssets,
     bullet: Bullet,
     translation: Vec3,
     direction: Direction,
-) {
-    let bullet_texture_handle = asset_server.load("textures/bullet.bmp");
-    let bullet_texture_layout = TextureAtlasLayout::from_grid(UVec2::new(7, 8), 4, 1, None, None);
-    commands.spawn((
-        bullet,
-        direction,
-        Sprite {
-            image: bullet_texture_handle.clone(),
-            texture_atlas: Some(TextureAtlas {
-                index: match direction {
-                    common::Direction::Up => 0,
-                    common::Direction::Right => 1,
-                    common::Direction::Down => 2,
-                    common::Direction::Left => 3,
-                },
-                layout: atlas_layouts.add(bullet_texture_layout),
-            }),
-            ..default()
-        },
-        Transform {
-            translation: Vec3::new(translation.x, translation.y, translation.z),
-            ..default()
-        },
-        Collider::cuboid(2.0, 2.0),
-        Sensor,
-        RigidBody::Dynamic,
-        ActiveEvents::COLLISION_EVENTS,
-    ));
+) -> Entity {
+    commands
+        .spawn((
+            bullet,
+            direction,
+            Sprite {
+                image: sprite_assets.bullet.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    index: match direction {
+                        common::Direction::Up => 0,
+                        common::Direction::Right => 1,
+                        common::Direction::Down => 2,
+                        common::Direction::Left => 3,
+                    },
+                    layout: sprite_assets.bullet_layout.clone(),
+                }),
+                ..default()
+            },
+            Transform {
+                translation: Vec3::new(translation.x, translation.y, translation.z),
+                ..default()
+            },
+            Collider::cuboid(2.0, 2.0),
+            Sensor,
+            RigidBody::Dynamic,
+            ActiveEvents::COLLISION_EVENTS,
+        ))
+        .id()
 }
 
 pub fn spawn_explosion(
     mut commands: Commands,
     mut explosion_er: EventReader<ExplosionEvent>,
     explosion_assets: Res<ExplosionAssets>,
-    asset_server: Res<AssetServer>,
-    mut textures: ResMut<Assets<Image>>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    game_sounds: Res<GameSounds>,
+    mut sound_ew: EventWriter<SoundEvent>,
 ) {
-    let mut big_explosion_texture_atlas_builder = TextureAtlasBuilder::default();
-    for handle in &explosion_assets.big_explosion {
-        let Some(texture) = textures.get(handle.id()) else {
-            warn!(
-                "{:?} did not resolve to an `Image` asset.",
-                asset_server.get_path(handle.id())
-            );
-            continue;
-        };
-        big_explosion_texture_atlas_builder.add_texture(Some(handle.id()), texture);
-    }
-    let big_explosion_texture_atlas = big_explosion_texture_atlas_builder.build().unwrap();
-    let big_explosion_atlas_layout_handle = atlas_layouts.add(big_explosion_texture_atlas.0);
-    let big_explosion_texture_handle = textures.add(big_explosion_texture_atlas.2);
-
-    let mut bullet_explosion_texture_atlas_builder = TextureAtlasBuilder::default();
-    for handle in &explosion_assets.bullet_explosion {
-        let Some(texture) = textures.get(handle.id()) else {
-            warn!(
-                "{:?} did not resolve to an `Image` asset.",
-                asset_server.get_path(handle.id())
-            );
-            continue;
-        };
-        bullet_explosion_texture_atlas_builder.add_texture(Some(handle.id()), texture);
-    }
-    let bullet_explosion_texture_atlas = bullet_explosion_texture_atlas_builder.build().unwrap();
-    let bullet_explosion_atlas_layout_handle = atlas_layouts.add(bullet_explosion_texture_atlas.0);
-    let bullet_explosion_texture_handle = textures.add(bullet_explosion_texture_atlas.2);
-
     for explosion in explosion_er.read() {
-        commands.spawn((
-            Explosion,
-            Sprite {
-                image: if explosion.explosion_type == ExplosionType::BigExplosion {
-                    big_explosion_texture_handle.clone()
-                } else {
-                    bullet_explosion_texture_handle.clone()
+        let atlas = if explosion.explosion_type == ExplosionType::BigExplosion {
+            &explosion_assets.big_explosion_atlas
+        } else {
+            &explosion_assets.bullet_explosion_atlas
+        };
+        // 图片还未加载完成时只播放音效
+        if let Some((image, layout)) = atlas {
+            commands.spawn((
+                Explosion,
+                Sprite {
+                    image: image.clone(),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: layout.clone(),
+                        index: 0,
+                    }),
+                    ..default()
                 },
-                texture_atlas: Some(TextureAtlas {
-                    layout: if explosion.explosion_type == ExplosionType::BigExplosion {
-                        big_explosion_atlas_layout_handle.clone()
+                Transform::from_translation(explosion.pos),
+                AnimationTimer(Timer::from_seconds(0.05, TimerMode::Repeating)),
+                AnimationIndices {
+                    first: 0,
+                    last: if explosion.explosion_type == ExplosionType::BigExplosion {
+                        4
                     } else {
-                        bullet_explosion_atlas_layout_handle.clone()
+                        2
                     },
-                    index: 0,
-                }),
-                ..default()
-            },
-            Transform::from_translation(explosion.pos),
-            AnimationTimer(Timer::from_seconds(0.05, TimerMode::Repeating)),
-            AnimationIndices {
-                first: 0,
-                last: if explosion.explosion_type == ExplosionType::BigExplosion {
-                    4
-                } else {
-                    2
                 },
-            },
-        ));
-        if explosion.explosion_type == ExplosionType::BigExplosion {
-            commands.spawn((
-                AudioPlayer(game_sounds.big_explosion.clone()),
-                PlaybackSettings::DESPAWN,
             ));
+        }
+        if explosion.explosion_type == ExplosionType::BigExplosion {
+            sound_ew.send(SoundEvent(Sound::BigExplosion));
         } else if explosion.explosion_type == ExplosionType::BulletExplosion {
-            commands.spawn((
-                AudioPlayer(game_sounds.bullet_explosion.clone()),
-                PlaybackSettings::DESPAWN,
-            ));
+            sound_ew.send(SoundEvent(Sound::BulletExplosion));
         }
     }
 }
@@ -0,0 +1,310 @@
+use bevy::prelude::*;
+use bevy::render::camera::{ScalingMode, Viewport};
+use bevy::render::view::RenderLayers;
+use bevy::ui::IsDefaultUiCamera;
+
+use crate::area::WALL_THICKNESS;
+use crate::common::{AppState, MultiplayerMode, TILE_SIZE, VIEW_HEIGHT, VIEW_WIDTH};
+use crate::level::LevelBounds;
+use crate::locale::Locale;
+use crate::player::{PlayerNo, RemotePlayer2};
+
+pub const VIEW_SIZE: Vec2 = Vec2::new(VIEW_WIDTH, VIEW_HEIGHT);
+// 分屏时每个画面的尺寸
+const SPLIT_VIEW_SIZE: Vec2 = Vec2::new(VIEW_WIDTH / 2., VIEW_HEIGHT);
+
+// 双人时玩家和画面边缘之间至少保留的距离
+const CAMERA_MARGIN: f32 = 4.0 * TILE_SIZE;
+// 共用画面需要缩小超过SPLIT_SCALE时分屏，缩小到MERGE_SCALE以内时合并，避免在临界距离来回切换
+const SPLIT_SCALE: f32 = 1.25;
+const MERGE_SCALE: f32 = 1.1;
+// UI单独使用一个镜头渲染，游戏画面的精灵不在这个图层
+const UI_RENDER_LAYER: usize = 1;
+
+// 显示关卡的镜头，分屏时为玩家1的画面
+#[derive(Debug, Component)]
+pub struct GameCamera;
+
+// 分屏时玩家2的画面
+#[derive(Debug, Component)]
+pub struct SplitCamera;
+
+// 只渲染UI，画面分屏时菜单和HUD仍然占满整个窗口
+#[derive(Debug, Component)]
+pub struct UiCamera;
+
+// 分屏时每个画面内的玩家信息
+#[derive(Debug, Component)]
+pub struct ViewportHudText(pub PlayerNo);
+
+// 分屏画面和分隔线
+#[derive(Debug, Component)]
+pub struct OnSplitScreen;
+
+#[derive(Debug, Resource, Default)]
+pub struct SplitScreen(pub bool);
+
+fn projection(view: Vec2) -> OrthographicProjection {
+    OrthographicProjection {
+        scaling_mode: ScalingMode::Fixed {
+            width: view.x,
+            height: view.y,
+        },
+        ..OrthographicProjection::default_2d()
+    }
+}
+
+pub fn setup_camera(mut commands: Commands) {
+    commands.spawn((Camera2d, projection(VIEW_SIZE), GameCamera));
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: 2,
+            clear_color: ClearColorConfig::None,
+            ..default()
+        },
+        RenderLayers::layer(UI_RENDER_LAYER),
+        IsDefaultUiCamera,
+        UiCamera,
+    ));
+}
+
+// 包括边界墙在内的关卡尺寸
+fn arena_size(level_bounds: &LevelBounds) -> Vec2 {
+    level_bounds.size() + 2. * WALL_THICKNESS
+}
+
+// 显示整个关卡时的镜头，关卡比画面小时不放大
+pub fn level_overview(level_bounds: &LevelBounds) -> (Vec2, f32) {
+    let scale = (arena_size(level_bounds) / VIEW_SIZE).max_element();
+    (Vec2::ZERO, scale.max(1.))
+}
+
+// 镜头中心和缩放：所有目标都在画面内，画面尽量不超出关卡边界。没有目标时返回None
+pub fn camera_view(
+    level_bounds: &LevelBounds,
+    view: Vec2,
+    targets: &[Vec2],
+    min_scale: f32,
+) -> Option<(Vec2, f32)> {
+    let first = *targets.first()?;
+    let (min, max) = targets.iter().fold((first, first), |(min, max), pos| {
+        (min.min(*pos), max.max(*pos))
+    });
+    let arena = arena_size(level_bounds);
+
+    let mut scale = min_scale;
+    if targets.len() > 1 {
+        // 缩小画面直到容纳所有目标，最多缩小到显示整个关卡
+        let fit_targets = ((max - min + 2. * CAMERA_MARGIN) / view).max_element();
+        let fit_level = (arena / view).max_element();
+        scale = scale.max(fit_targets.min(fit_level));
+    }
+
+    let half_view = view * scale / 2.;
+    let half_arena = arena / 2.;
+    let center = (min + max) / 2.;
+    let clamp = |center: f32, half_view: f32, half_arena: f32| {
+        if half_arena <= half_view {
+            0.
+        } else {
+            center.clamp(half_view - half_arena, half_arena - half_view)
+        }
+    };
+    Some((
+        Vec2::new(
+            clamp(center.x, half_view.x, half_arena.x),
+            clamp(center.y, half_view.y, half_arena.y),
+        ),
+        scale,
+    ))
+}
+
+fn player_position(q_players: &Query<(&PlayerNo, &Transform)>, no: u32) -> Option<Vec2> {
+    q_players
+        .iter()
+        .find(|(player_no, _)| player_no.0 == no)
+        .map(|(_, transform)| transform.translation.truncate())
+}
+
+// 本地双人对局中，两个玩家距离过远、共用画面需要缩小太多时分屏
+pub fn update_split_screen(
+    app_state: Res<State<AppState>>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    remote_player2: Option<Res<RemotePlayer2>>,
+    level_bounds: Res<LevelBounds>,
+    q_players: Query<(&PlayerNo, &Transform)>,
+    mut split_screen: ResMut<SplitScreen>,
+) {
+    let split = match app_state.get() {
+        AppState::Playing
+            if *multiplayer_mode == MultiplayerMode::TwoPlayers && remote_player2.is_none() =>
+        {
+            match (
+                player_position(&q_players, 1),
+                player_position(&q_players, 2),
+            ) {
+                (Some(player1), Some(player2)) => {
+                    let (_, scale) =
+                        camera_view(&level_bounds, VIEW_SIZE, &[player1, player2], 1.).unwrap();
+                    if split_screen.0 {
+                        scale > MERGE_SCALE
+                    } else {
+                        scale > SPLIT_SCALE
+                    }
+                }
+                // 有玩家正在重生时保持不变
+                _ => split_screen.0,
+            }
+        }
+        AppState::Paused => split_screen.0,
+        _ => false,
+    };
+    if split_screen.0 != split {
+        info!("Split screen: {}", split);
+        split_screen.0 = split;
+    }
+}
+
+// 切换分屏时生成或移除玩家2的镜头和各画面的HUD
+pub fn apply_split_screen(
+    mut commands: Commands,
+    split_screen: Res<SplitScreen>,
+    locale: Res<Locale>,
+    mut q_game_camera: Query<(Entity, &mut OrthographicProjection), With<GameCamera>>,
+    q_on_split_screen: Query<Entity, With<OnSplitScreen>>,
+) {
+    if !split_screen.is_changed() {
+        return;
+    }
+    for entity in &q_on_split_screen {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Ok((game_camera, mut game_projection)) = q_game_camera.get_single_mut() else {
+        return;
+    };
+    if !split_screen.0 {
+        *game_projection = projection(VIEW_SIZE);
+        return;
+    }
+    *game_projection = projection(SPLIT_VIEW_SIZE);
+    let split_camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            projection(SPLIT_VIEW_SIZE),
+            SplitCamera,
+            OnSplitScreen,
+        ))
+        .id();
+
+    for (camera, player_no) in [(game_camera, PlayerNo(1)), (split_camera, PlayerNo(2))] {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(40.),
+                left: Val::Px(30.),
+                ..default()
+            },
+            Text::default(),
+            locale.text_font(18.),
+            TextColor(Color::WHITE),
+            TargetCamera(camera),
+            ViewportHudText(player_no),
+            OnSplitScreen,
+        ));
+    }
+    // 分隔线，在UI镜头中绘制
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(50.),
+            margin: UiRect::left(Val::Px(-2.)),
+            width: Val::Px(4.),
+            height: Val::Percent(100.),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+        OnSplitScreen,
+    ));
+}
+
+// 本地对局镜头跟随玩家，分屏时两个镜头各自跟随一个玩家。玩家都在重生时镜头停在原地
+pub fn follow_players_camera(
+    level_bounds: Res<LevelBounds>,
+    split_screen: Res<SplitScreen>,
+    q_players: Query<(&PlayerNo, &Transform)>,
+    mut q_game_camera: Query<
+        (&mut Transform, &mut OrthographicProjection),
+        (With<GameCamera>, Without<SplitCamera>, Without<PlayerNo>),
+    >,
+    mut q_split_camera: Query<
+        (&mut Transform, &mut OrthographicProjection),
+        (With<SplitCamera>, Without<GameCamera>, Without<PlayerNo>),
+    >,
+) {
+    let views = if split_screen.0 {
+        let player1: Vec<Vec2> = player_position(&q_players, 1).into_iter().collect();
+        let player2: Vec<Vec2> = player_position(&q_players, 2).into_iter().collect();
+        [
+            camera_view(&level_bounds, SPLIT_VIEW_SIZE, &player1, 1.),
+            camera_view(&level_bounds, SPLIT_VIEW_SIZE, &player2, 1.),
+        ]
+    } else {
+        let targets: Vec<Vec2> = q_players
+            .iter()
+            .map(|(_, transform)| transform.translation.truncate())
+            .collect();
+        [camera_view(&level_bounds, VIEW_SIZE, &targets, 1.), None]
+    };
+    let cameras = q_game_camera.iter_mut().chain(q_split_camera.iter_mut());
+    for ((mut transform, mut projection), view) in cameras.zip(views) {
+        let Some((center, scale)) = view else {
+            continue;
+        };
+        if transform.translation.truncate() != center {
+            transform.translation = center.extend(transform.translation.z);
+        }
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+// 离开对局后镜头回到原点
+pub fn reset_camera(
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    for (mut transform, mut projection) in &mut q_camera {
+        transform.translation = Vec3::ZERO;
+        projection.scale = 1.;
+    }
+}
+
+// 分屏时游戏画面左右各占一半，UI镜头占满整个画面
+pub fn camera_viewport(
+    position: UVec2,
+    size: UVec2,
+    split: bool,
+    ui_camera: bool,
+    split_camera: bool,
+) -> Viewport {
+    let half_width = size.x / 2;
+    let (physical_position, physical_size) = match (split && !ui_camera, split_camera) {
+        (false, _) => (position, size),
+        (true, false) => (position, UVec2::new(half_width, size.y)),
+        (true, true) => (
+            position + UVec2::new(half_width, 0),
+            UVec2::new(size.x - half_width, size.y),
+        ),
+    };
+    Viewport {
+        physical_position,
+        physical_size,
+        ..default()
+    }
+}
@@ -0,0 +1,273 @@
+use bevy::core::FrameCount;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::{raw_level_accessor::RawLevelAccessor, LdtkJson};
+use std::path::Path;
+
+use crate::common::{AppState, MultiplayerMode, MAX_LEVELS};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::lint::{levels_from_ldtk, LintLevel};
+use crate::map::LevelMap;
+use crate::net::DEFAULT_PORT;
+
+pub const USAGE: &str = "\
+Usage: battle-city [OPTIONS]
+
+Options:
+  --levels <PATH>     .ldtk file inside assets/, a custom level text file,
+                      or a directory of custom level text files (level pack)
+  --level <INDEX>     start at the given level index (0 based)
+  --players <1|2>     number of players
+  --headless          run without window and rendering; .ldtk levels are
+                      converted to custom levels (entities only, no tilemap)
+  --frames <N>        exit after N frames
+  --host              host a LAN game and wait for player 2
+  --join <ADDR>       join a LAN game at ADDR (host:port)
+  --spectate <ADDR>   watch a LAN game at ADDR without playing
+  --port <PORT>       UDP port to host on (default 7878)
+  --versus            LAN game in versus mode
+  --survival          endless survival mode on a single level
+  --event-log <PATH>  write gameplay events to PATH as JSON Lines
+  --dev               enable developer tools (F3 debug overlay, ` console)
+  -h, --help          print this help";
+
+// 命令行启动参数，指定关卡、关卡序号或玩家数量时跳过开始菜单
+#[derive(Debug, Resource, Default, Clone)]
+pub struct LaunchOptions {
+    pub levels: Option<String>,
+    pub start_level: Option<usize>,
+    pub players: Option<MultiplayerMode>,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub host: bool,
+    pub join: Option<String>,
+    pub spectate: Option<String>,
+    pub port: Option<u16>,
+    pub versus: bool,
+    pub survival: bool,
+    pub event_log: Option<String>,
+    // 开启F3调试信息和开发者控制台
+    pub dev: bool,
+    pub help: bool,
+}
+
+impl LaunchOptions {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "--levels" => options.levels = Some(value("--levels")?),
+                "--level" => {
+                    let index = value("--level")?;
+                    options.start_level = Some(
+                        index
+                            .parse()
+                            .map_err(|_| format!("invalid level index: {}", index))?,
+                    );
+                }
+                "--players" => {
+                    options.players = Some(match value("--players")?.as_str() {
+                        "1" => MultiplayerMode::SinglePlayer,
+                        "2" => MultiplayerMode::TwoPlayers,
+                        players => return Err(format!("invalid players: {}", players)),
+                    });
+                }
+                "--headless" => options.headless = true,
+                "--frames" => {
+                    let frames = value("--frames")?;
+                    options.frames = Some(
+                        frames
+                            .parse()
+                            .map_err(|_| format!("invalid frames: {}", frames))?,
+                    );
+                }
+                "--host" => options.host = true,
+                "--join" => options.join = Some(value("--join")?),
+                "--spectate" => options.spectate = Some(value("--spectate")?),
+                "--port" => {
+                    let port = value("--port")?;
+                    options.port = Some(
+                        port.parse()
+                            .map_err(|_| format!("invalid port: {}", port))?,
+                    );
+                }
+                "--versus" => options.versus = true,
+                "--survival" => options.survival = true,
+                "--event-log" => options.event_log = Some(value("--event-log")?),
+                "--dev" => options.dev = true,
+                "-h" | "--help" => options.help = true,
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+        if [
+            options.host,
+            options.join.is_some(),
+            options.spectate.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+            > 1
+        {
+            return Err("only one of --host, --join and --spectate can be used".to_string());
+        }
+        Ok(options)
+    }
+
+    pub fn skip_start_menu(&self) -> bool {
+        self.levels.is_some()
+            || self.start_level.is_some()
+            || self.players.is_some()
+            || self.headless
+            || self.survival
+            || self.lan_game()
+    }
+
+    // 命令行指定了创建主机或加入时直接进入联机大厅
+    pub fn lan_game(&self) -> bool {
+        self.host || self.join.is_some() || self.spectate.is_some()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    pub fn start_level(&self) -> usize {
+        self.start_level.unwrap_or(0)
+    }
+
+    // ldtk文件路径相对于assets目录
+    pub fn ldtk_asset_path(&self) -> Option<String> {
+        self.levels
+            .as_ref()
+            .filter(|path| path.ends_with(".ldtk"))
+            .map(|path| path.strip_prefix("assets/").unwrap_or(path).to_string())
+    }
+
+    // 读取自定义关卡文件，目录中的关卡文件按文件名排序组成关卡包
+    pub fn load_level_pack(&self) -> Result<Vec<LevelMap>, String> {
+        let Some(path) = self.levels.as_ref().filter(|path| !path.ends_with(".ldtk")) else {
+            // 无渲染时没有ldtk插件，把ldtk关卡转换成自定义关卡
+            #[cfg(not(target_arch = "wasm32"))]
+            if self.headless {
+                return self.load_ldtk_pack();
+            }
+            return Ok(Vec::new());
+        };
+        let maps = if Path::new(path).is_dir() {
+            Self::load_level_dir(path)?
+        } else {
+            vec![LevelMap::load(path)?]
+        };
+        if self.start_level() >= maps.len() {
+            return Err(format!(
+                "{}: level index {} out of range, {} level(s) found",
+                path,
+                self.start_level(),
+                maps.len()
+            ));
+        }
+        Ok(maps)
+    }
+
+    // 检查--level是否超出ldtk文件中的关卡数量，ldtk文件相对于assets目录
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn validate_ldtk_start_level(&self) -> Result<(), String> {
+        if self.start_level.is_none() || self.levels.is_some() && self.ldtk_asset_path().is_none() {
+            return Ok(());
+        }
+        let (asset_path, path) = self.ldtk_file_path();
+        let text =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let count = ldtk_level_count(&text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .min(MAX_LEVELS as usize);
+        if self.start_level() >= count {
+            return Err(format!(
+                "{}: level index {} out of range, {} level(s) playable",
+                asset_path,
+                self.start_level(),
+                count
+            ));
+        }
+        Ok(())
+    }
+
+    // ldtk文件相对于assets目录的路径和完整路径
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ldtk_file_path(&self) -> (String, std::path::PathBuf) {
+        let asset_path = self.ldtk_asset_path().unwrap_or("levels.ldtk".to_string());
+        let path = bevy::asset::io::file::FileAssetReader::get_base_path()
+            .join("assets")
+            .join(&asset_path);
+        (asset_path, path)
+    }
+
+    // 只保留ldtk关卡中的实体，和有渲染时一样最多MAX_LEVELS关
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_ldtk_pack(&self) -> Result<Vec<LevelMap>, String> {
+        let (_, path) = self.ldtk_file_path();
+        let text =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let levels = levels_from_ldtk(&text, path.parent().unwrap_or(Path::new("")))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(levels
+            .iter()
+            .take(MAX_LEVELS as usize)
+            .map(LintLevel::to_map)
+            .collect())
+    }
+
+    fn load_level_dir(path: &str) -> Result<Vec<LevelMap>, String> {
+        let mut files = std::fs::read_dir(path)
+            .map_err(|e| format!("{}: {}", path, e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|ext| ext == "txt"))
+            .collect::<Vec<_>>();
+        files.sort();
+        if files.is_empty() {
+            return Err(format!("{}: no level files (*.txt) found", path));
+        }
+        files
+            .iter()
+            .map(|file| LevelMap::load(&file.to_string_lossy()))
+            .collect()
+    }
+}
+
+// ldtk文件中的关卡数量
+pub fn ldtk_level_count(text: &str) -> Result<usize, String> {
+    let ldtk: LdtkJson = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    Ok(ldtk.iter_raw_levels().count())
+}
+
+pub fn skip_start_menu(
+    launch_options: Res<LaunchOptions>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if launch_options.skip_start_menu() {
+        info!("Skip start menu, launch options: {:?}", *launch_options);
+        app_state.set(if launch_options.lan_game() {
+            AppState::Lobby
+        } else {
+            AppState::Playing
+        });
+    }
+}
+
+pub fn exit_after_frames(
+    launch_options: Res<LaunchOptions>,
+    frame_count: Res<FrameCount>,
+    mut app_exit_ew: EventWriter<AppExit>,
+) {
+    if launch_options
+        .frames
+        .is_some_and(|frames| frame_count.0 >= frames)
+    {
+        app_exit_ew.send(AppExit::Success);
+    }
+}
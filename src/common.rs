@@ -1,9 +1,13 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 // 关卡地图行数和列数
 pub const LEVEL_ROWS: i32 = 18;
 pub const LEVEL_COLUMNS: i32 = 27;
 pub const TILE_SIZE: f32 = 32.0;
+// 画面的设计分辨率，包含战场和周围的HUD，窗口缩放时保持比例并加黑边
+pub const VIEW_WIDTH: f32 = 1280.0;
+pub const VIEW_HEIGHT: f32 = 720.0;
 // 关卡数量
 pub const MAX_LEVELS: i32 = 2;
 // 同时共存的敌人最大数量
@@ -17,6 +21,8 @@ pub const ENEMY_REFRESH_BULLET_INTERVAL: f32 = 2.0;
 pub const PLAYER_SPEED: f32 = 150.0;
 pub const ENEMY_SPEED: f32 = 100.0;
 pub const TANK_SIZE: u32 = 28;
+// 击毁一辆敌人坦克的得分
+pub const ENEMY_SCORE: u32 = 100;
 pub const TANK_SCALE: f32 = 0.8;
 
 // sprite z轴顺序
@@ -30,17 +36,58 @@ pub enum AppState {
     StartMenu,
     Playing,
     Paused,
+    Restarting,
+    // 通关后播放胜利音乐，结束后切换关卡
+    StageClear,
     GameOver,
+    // 通关最后一关或关卡包后显示胜利画面并记录最高分
+    Victory,
+    Editor,
+    StageSelect,
+    HighScores,
+    Options,
+    // 局域网联机大厅，创建主机或加入
+    Lobby,
+    // 联机客户端，显示主机发送的画面
+    NetClient,
 }
 
-#[derive(Resource, Debug, PartialEq, Eq)]
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MultiplayerMode {
     SinglePlayer,
     TwoPlayers,
 }
 
+impl MultiplayerMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            MultiplayerMode::SinglePlayer => "1P",
+            MultiplayerMode::TwoPlayers => "2P",
+        }
+    }
+}
+
+// 双人对战模式，玩家的子弹可以击毁另一名玩家
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VersusMode(pub bool);
+
+// 游戏难度，目前只有普通难度
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    #[default]
+    Normal,
+}
+
+impl Difficulty {
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Normal => "NORMAL",
+        }
+    }
+}
+
 // 方向
-#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Left,
     Right,
@@ -63,24 +110,3 @@ pub struct TankRefreshBulletTimer(pub Timer);
 
 #[derive(Default, Event)]
 pub struct HomeDyingEvent;
-
-#[derive(Debug, Resource)]
-pub struct GameSounds {
-    pub mode_switch: Handle<AudioSource>,
-    pub bullet_explosion: Handle<AudioSource>,
-    pub big_explosion: Handle<AudioSource>,
-    pub player_fire: Handle<AudioSource>,
-    pub game_over: Handle<AudioSource>,
-    pub game_pause: Handle<AudioSource>,
-}
-
-pub fn setup_game_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.insert_resource(GameSounds {
-        mode_switch: asset_server.load("sounds/mode_switch.ogg"),
-        bullet_explosion: asset_server.load("sounds/bullet_explosion.ogg"),
-        big_explosion: asset_server.load("sounds/big_explosion.ogg"),
-        player_fire: asset_server.load("sounds/player_fire.ogg"),
-        game_over: asset_server.load("sounds/game_over.ogg"),
-        game_pause: asset_server.load("sounds/game_pause.ogg"),
-    });
-}
@@ -0,0 +1,485 @@
+use bevy::diagnostic::{
+    DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
+};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use bevy_rapier2d::render::DebugRenderContext;
+use rand::Rng;
+
+use crate::bullet::Bullet;
+use crate::cli::LaunchOptions;
+use crate::common::{self, MultiplayerMode, ENEMIES_PER_LEVEL, ENEMY_SPEED, TILE_SIZE};
+use crate::enemy::{spawn_enemy, Enemy, EnemyChangeDirectionTimer, EnemyKind, LevelSpawnedEnemies};
+use crate::event_log::{GameEvent, PowerUpKind};
+use crate::level::{EnemiesMarker, LevelItem};
+use crate::locale::Locale;
+use crate::player::{
+    spawn_shield, Born, PlayerInputs, PlayerLives, PlayerNo, Shield, ShieldRemoveTimer,
+};
+use crate::sprites::SpriteAssets;
+use crate::survival::SurvivalMode;
+
+// 开发者工具（--dev启动时可用）：F3切换调试信息（碰撞体轮廓、敌人方向和转向计时、帧率和实体数量），
+// `键打开控制台输入命令
+const CONSOLE_LINES: usize = 8;
+const MAX_INPUT_LEN: usize = 60;
+const DEBUG_COLOR: Color = Color::srgb(0.2, 1.0, 0.2);
+
+pub const CONSOLE_HELP: &str = "\
+commands: spawn_enemy, god, skip_level, give <shield|life>, set lives <N>, clear, help";
+
+#[derive(Debug, Resource, Default)]
+pub struct DebugOverlay(pub bool);
+
+// 无敌模式，玩家被击中时和有保护盾一样
+#[derive(Debug, Resource, Default)]
+pub struct GodMode(pub bool);
+
+// 本局使用过控制台作弊命令，得分不计入排行榜，和得分一起重置
+#[derive(Debug, Resource, Default)]
+pub struct CheatsUsed(pub bool);
+
+#[derive(Debug, Resource, Default)]
+pub struct DevConsole {
+    pub open: bool,
+    pub input: String,
+    pub lines: Vec<String>,
+}
+
+impl DevConsole {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+        if self.lines.len() > CONSOLE_LINES {
+            self.lines.remove(0);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUp {
+    Shield,
+    Life,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub enum ConsoleCommand {
+    SpawnEnemy,
+    God,
+    SkipLevel,
+    Give(PowerUp),
+    SetLives(i8),
+    Clear,
+    Help,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<ConsoleCommand, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["spawn_enemy"] => Ok(ConsoleCommand::SpawnEnemy),
+            ["god"] => Ok(ConsoleCommand::God),
+            ["skip_level"] => Ok(ConsoleCommand::SkipLevel),
+            ["give", "shield"] => Ok(ConsoleCommand::Give(PowerUp::Shield)),
+            ["give", "life"] => Ok(ConsoleCommand::Give(PowerUp::Life)),
+            ["give", power_up] => Err(format!(
+                "unknown power-up: {} (available: shield, life)",
+                power_up
+            )),
+            ["set", "lives", lives] => lives
+                .parse()
+                .ok()
+                .filter(|lives| *lives >= 0)
+                .map(ConsoleCommand::SetLives)
+                .ok_or_else(|| format!("invalid lives: {}", lives)),
+            ["clear"] => Ok(ConsoleCommand::Clear),
+            ["help"] => Ok(ConsoleCommand::Help),
+            _ => Err(format!("unknown command: {}", line.trim())),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OnDebugOverlay;
+
+#[derive(Component)]
+pub struct DebugStatsText;
+
+// 显示敌人转向计时的文字，跟随对应的敌人
+#[derive(Component)]
+pub struct DebugLabel(pub Entity);
+
+#[derive(Component)]
+pub struct OnDevConsole;
+
+#[derive(Component)]
+pub struct DevConsoleText;
+
+pub fn dev_tools_enabled(launch_options: Res<LaunchOptions>) -> bool {
+    launch_options.dev
+}
+
+pub fn debug_overlay_enabled(debug_overlay: Res<DebugOverlay>) -> bool {
+    debug_overlay.0
+}
+
+pub fn console_closed(dev_console: Res<DevConsole>) -> bool {
+    !dev_console.open
+}
+
+pub fn toggle_debug_overlay(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut debug_overlay: ResMut<DebugOverlay>,
+    debug_render_context: Option<ResMut<DebugRenderContext>>,
+    q_overlay: Query<Entity, With<OnDebugOverlay>>,
+    locale: Res<Locale>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    debug_overlay.0 = !debug_overlay.0;
+    // 无渲染运行时没有rapier调试渲染
+    if let Some(mut debug_render_context) = debug_render_context {
+        debug_render_context.enabled = debug_overlay.0;
+    }
+    if debug_overlay.0 {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                left: Val::Px(10.),
+                ..default()
+            },
+            Text::default(),
+            locale.text_font(14.),
+            TextColor(DEBUG_COLOR),
+            GlobalZIndex(10),
+            OnDebugOverlay,
+            DebugStatsText,
+        ));
+    } else {
+        for entity in &q_overlay {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn update_debug_stats(
+    diagnostics: Res<DiagnosticsStore>,
+    mut q_text: Query<&mut Text, With<DebugStatsText>>,
+    q_players: Query<(), (With<PlayerNo>, Without<Born>)>,
+    q_enemies: Query<(), With<Enemy>>,
+    q_bullets: Query<(), With<Bullet>>,
+    q_level_items: Query<(), With<LevelItem>>,
+    god_mode: Res<GodMode>,
+) {
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or(0.);
+    let entities = diagnostics
+        .get(&EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+        .and_then(|count| count.value())
+        .unwrap_or(0.);
+    for mut text in &mut q_text {
+        text.0 = format!(
+            "FPS {:.0}\nentities {:.0}\nplayers {}\nenemies {}\nbullets {}\nlevel items {}{}",
+            fps,
+            entities,
+            q_players.iter().len(),
+            q_enemies.iter().len(),
+            q_bullets.iter().len(),
+            q_level_items.iter().len(),
+            if god_mode.0 { "\nGOD MODE" } else { "" },
+        );
+    }
+}
+
+// 敌人当前方向，以及转向计时结束时到达的位置（下一次选择方向的地点）
+pub fn draw_enemy_debug(
+    mut gizmos: Gizmos,
+    q_enemies: Query<(&Transform, &common::Direction, &EnemyChangeDirectionTimer), With<Enemy>>,
+) {
+    for (transform, direction, timer) in &q_enemies {
+        let pos = transform.translation.truncate();
+        let dir = direction_vec(*direction);
+        gizmos.arrow_2d(pos, pos + dir * TILE_SIZE, DEBUG_COLOR);
+        let target = pos + dir * ENEMY_SPEED * timer.0.remaining_secs();
+        gizmos.line_2d(pos, target, Color::srgba(1.0, 1.0, 0.0, 0.5));
+        gizmos.circle_2d(
+            Isometry2d::from_translation(target),
+            4.,
+            Color::srgb(1.0, 1.0, 0.0),
+        );
+    }
+}
+
+fn direction_vec(direction: common::Direction) -> Vec2 {
+    match direction {
+        common::Direction::Up => Vec2::Y,
+        common::Direction::Right => Vec2::X,
+        common::Direction::Down => Vec2::NEG_Y,
+        common::Direction::Left => Vec2::NEG_X,
+    }
+}
+
+pub fn update_debug_labels(
+    mut commands: Commands,
+    q_enemies: Query<(Entity, &Transform, &EnemyChangeDirectionTimer), With<Enemy>>,
+    mut q_labels: Query<(Entity, &DebugLabel, &mut Text2d, &mut Transform), Without<Enemy>>,
+    locale: Res<Locale>,
+) {
+    let mut labeled = Vec::new();
+    for (label_entity, label, mut text, mut transform) in &mut q_labels {
+        let Ok((_, enemy_transform, timer)) = q_enemies.get(label.0) else {
+            commands.entity(label_entity).despawn();
+            continue;
+        };
+        text.0 = format!("{:.1}", timer.0.remaining_secs());
+        transform.translation = label_translation(enemy_transform);
+        labeled.push(label.0);
+    }
+    for (entity, transform, _) in &q_enemies {
+        if !labeled.contains(&entity) {
+            commands.spawn((
+                Text2d::default(),
+                locale.text_font(12.),
+                TextColor(DEBUG_COLOR),
+                Transform::from_translation(label_translation(transform)),
+                OnDebugOverlay,
+                DebugLabel(entity),
+            ));
+        }
+    }
+}
+
+fn label_translation(enemy_transform: &Transform) -> Vec3 {
+    (enemy_transform.translation.truncate() + Vec2::new(0., TILE_SIZE * 0.75)).extend(10.)
+}
+
+pub fn dev_console_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut keyboard_er: EventReader<KeyboardInput>,
+    mut dev_console: ResMut<DevConsole>,
+    mut player_inputs: ResMut<PlayerInputs>,
+    mut console_command_ew: EventWriter<ConsoleCommand>,
+    q_console: Query<Entity, With<OnDevConsole>>,
+    locale: Res<Locale>,
+) {
+    let typed: Vec<Key> = keyboard_er
+        .read()
+        .filter(|event| event.state.is_pressed())
+        .map(|event| event.logical_key.clone())
+        .collect();
+    if !dev_console.open {
+        if keyboard_input.just_pressed(KeyCode::Backquote) {
+            dev_console.open = true;
+            // 打开控制台时松开所有按键，避免坦克继续移动
+            *player_inputs = PlayerInputs::default();
+            spawn_dev_console(&mut commands, &locale);
+        }
+        return;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Backquote, KeyCode::Escape]) {
+        dev_console.open = false;
+        for entity in &q_console {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    for key in typed {
+        match key {
+            Key::Character(text) => {
+                for c in text.chars() {
+                    if c != '`' && !c.is_control() && dev_console.input.len() < MAX_INPUT_LEN {
+                        dev_console.input.push(c);
+                    }
+                }
+            }
+            Key::Space if dev_console.input.len() < MAX_INPUT_LEN => {
+                dev_console.input.push(' ');
+            }
+            Key::Backspace => {
+                dev_console.input.pop();
+            }
+            Key::Enter => {
+                let line = std::mem::take(&mut dev_console.input);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                dev_console.print(format!("> {}", line));
+                match ConsoleCommand::parse(&line) {
+                    Ok(command) => {
+                        console_command_ew.send(command);
+                    }
+                    Err(e) => dev_console.print(e),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spawn_dev_console(commands: &mut Commands, locale: &Locale) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.),
+                left: Val::Px(0.),
+                width: Val::Percent(100.),
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+            GlobalZIndex(10),
+            OnDevConsole,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                locale.text_font(16.),
+                TextColor(DEBUG_COLOR),
+                DevConsoleText,
+            ));
+        });
+}
+
+pub fn update_dev_console(
+    dev_console: Res<DevConsole>,
+    mut q_text: Query<&mut Text, With<DevConsoleText>>,
+) {
+    for mut text in &mut q_text {
+        let mut lines = dev_console.lines.clone();
+        lines.push(format!("> {}_", dev_console.input));
+        text.0 = lines.join("\n");
+    }
+}
+
+pub fn execute_console_commands(
+    mut commands: Commands,
+    mut console_command_er: EventReader<ConsoleCommand>,
+    mut dev_console: ResMut<DevConsole>,
+    mut god_mode: ResMut<GodMode>,
+    mut cheats_used: ResMut<CheatsUsed>,
+    survival_mode: Res<SurvivalMode>,
+    mut player_lives: ResMut<PlayerLives>,
+    mut level_spawned_enemies: ResMut<LevelSpawnedEnemies>,
+    q_enemies: Query<Entity, With<Enemy>>,
+    q_enemies_marker: Query<&GlobalTransform, With<EnemiesMarker>>,
+    // 出生动画实体也带有PlayerNo，护盾只能加到已经生成的坦克上
+    q_players: Query<(Entity, &PlayerNo, Option<&Children>), Without<Born>>,
+    mut q_shields: Query<&mut ShieldRemoveTimer, With<Shield>>,
+    sprite_assets: Res<SpriteAssets>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    mut game_event_ew: EventWriter<GameEvent>,
+) {
+    for command in console_command_er.read() {
+        match command {
+            ConsoleCommand::SkipLevel if survival_mode.0 => {
+                dev_console.print("skip_level is not available in survival mode");
+                continue;
+            }
+            ConsoleCommand::Clear | ConsoleCommand::Help => {}
+            _ => cheats_used.0 = true,
+        }
+        match command {
+            ConsoleCommand::SpawnEnemy => {
+                let markers: Vec<Vec3> = q_enemies_marker
+                    .iter()
+                    .map(|marker| marker.translation())
+                    .filter(|translation| *translation != Vec3::ZERO)
+                    .collect();
+                if markers.is_empty() {
+                    dev_console.print("no enemy spawn point in this level");
+                    continue;
+                }
+                let pos = markers[rand::thread_rng().gen_range(0..markers.len())];
+                spawn_enemy(pos, EnemyKind::Basic, &mut commands, &sprite_assets);
+                dev_console.print("enemy spawned");
+            }
+            ConsoleCommand::God => {
+                god_mode.0 = !god_mode.0;
+                dev_console.print(if god_mode.0 {
+                    "god mode on"
+                } else {
+                    "god mode off"
+                });
+            }
+            ConsoleCommand::SkipLevel => {
+                // 敌人全部生成并阵亡后check_stage_clear进入通关
+                for entity in &q_enemies {
+                    commands.entity(entity).despawn_recursive();
+                }
+                level_spawned_enemies.0 = ENEMIES_PER_LEVEL;
+                dev_console.print("level skipped");
+            }
+            ConsoleCommand::Give(PowerUp::Shield) => {
+                if q_players.is_empty() {
+                    dev_console.print("no player");
+                    continue;
+                }
+                for (player, player_no, children) in &q_players {
+                    let shield = children
+                        .into_iter()
+                        .flatten()
+                        .find(|child| q_shields.contains(**child));
+                    if let Some(shield) = shield {
+                        q_shields.get_mut(*shield).unwrap().0.reset();
+                    } else {
+                        let shield = spawn_shield(&mut commands, &sprite_assets);
+                        commands.entity(player).add_child(shield);
+                    }
+                    game_event_ew.send(GameEvent::PowerUp {
+                        player: player_no.0,
+                        kind: PowerUpKind::Shield,
+                    });
+                }
+                dev_console.print("shield given");
+            }
+            ConsoleCommand::Give(PowerUp::Life) => {
+                player_lives.player1 = player_lives.player1.saturating_add(1);
+                player_lives.player2 = player_lives.player2.saturating_add(1);
+                let players = match *multiplayer_mode {
+                    MultiplayerMode::SinglePlayer => 1,
+                    MultiplayerMode::TwoPlayers => 2,
+                };
+                for player in 1..=players {
+                    game_event_ew.send(GameEvent::PowerUp {
+                        player,
+                        kind: PowerUpKind::ExtraLife,
+                    });
+                }
+                dev_console.print("extra life given");
+            }
+            ConsoleCommand::SetLives(lives) => {
+                player_lives.player1 = *lives;
+                player_lives.player2 = *lives;
+                dev_console.print(format!("lives set to {}", lives));
+            }
+            ConsoleCommand::Clear => dev_console.lines.clear(),
+            ConsoleCommand::Help => dev_console.print(CONSOLE_HELP),
+        }
+    }
+}
+
+pub fn close_dev_console(
+    mut commands: Commands,
+    mut dev_console: ResMut<DevConsole>,
+    q_console: Query<Entity, With<OnDevConsole>>,
+) {
+    dev_console.open = false;
+    dev_console.input.clear();
+    for entity in &q_console {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn reset_cheats_used(mut cheats_used: ResMut<CheatsUsed>) {
+    cheats_used.0 = false;
+}
+
+pub fn reset_god_mode(mut god_mode: ResMut<GodMode>) {
+    god_mode.0 = false;
+}
@@ -0,0 +1,405 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::audio::{Sound, SoundEvent};
+use crate::camera::GameCamera;
+use crate::common::{AppState, MultiplayerMode, LEVEL_COLUMNS, LEVEL_ROWS, TILE_SIZE};
+use crate::generator::generate_level;
+use crate::level::LevelBounds;
+use crate::locale::{Locale, TextKey};
+use crate::map::{tile_local_translation, CustomLevel, LevelMap, MapTile, CUSTOM_LEVEL_FILE};
+use crate::sprites::SpriteAssets;
+use crate::ui::MENU_SELECTED_COLOR;
+
+// 调色板顺序，数字键1-9对应
+pub const EDITOR_PALETTE: [MapTile; 9] = [
+    MapTile::StoneWall,
+    MapTile::IronWall,
+    MapTile::Water,
+    MapTile::Tree,
+    MapTile::Home,
+    MapTile::Player1,
+    MapTile::Player2,
+    MapTile::Enemies,
+    MapTile::Empty,
+];
+
+const EDITOR_EMPTY_TILE_COLOR: Color = Color::srgb(0.1, 0.1, 0.1);
+
+#[derive(Component)]
+pub struct OnEditorScreen;
+
+// 编辑器中的格子
+#[derive(Component)]
+pub struct EditorTile {
+    pub col: i32,
+    pub row: i32,
+}
+
+#[derive(Component)]
+pub struct EditorCursor;
+
+#[derive(Component)]
+pub struct EditorPaletteItem(pub usize);
+
+#[derive(Component)]
+pub struct EditorStatusText;
+
+// 正在编辑的关卡，试玩返回后保留
+#[derive(Debug, Resource)]
+pub struct EditorLevel {
+    pub map: LevelMap,
+    pub cursor: IVec2,
+    pub selected: usize,
+    // 状态提示和附加信息，显示时按当前语言翻译
+    pub status: (TextKey, String),
+}
+
+// 当前对局是否为编辑器试玩
+#[derive(Debug, Resource, Default)]
+pub struct EditorTestPlay(pub bool);
+
+pub fn setup_editor(
+    mut commands: Commands,
+    sprite_assets: Res<SpriteAssets>,
+    editor_level: Option<ResMut<EditorLevel>>,
+    mut editor_test_play: ResMut<EditorTestPlay>,
+    mut custom_level: ResMut<CustomLevel>,
+    locale: Res<Locale>,
+) {
+    editor_test_play.0 = false;
+    custom_level.0 = None;
+
+    let map = match editor_level {
+        Some(mut editor_level) => {
+            // 试玩返回时刷新界面文字
+            editor_level.set_changed();
+            editor_level.map.clone()
+        }
+        None => {
+            let (map, status) = match LevelMap::load_stored(CUSTOM_LEVEL_FILE) {
+                Ok(map) => (map, (TextKey::EditorLoaded, CUSTOM_LEVEL_FILE.to_string())),
+                Err(_) => (
+                    LevelMap::with_base(LEVEL_COLUMNS, LEVEL_ROWS),
+                    (TextKey::EditorNewLevel, String::new()),
+                ),
+            };
+            commands.insert_resource(EditorLevel {
+                map: map.clone(),
+                cursor: IVec2::ZERO,
+                selected: 0,
+                status,
+            });
+            map
+        }
+    };
+
+    spawn_editor_tiles(&mut commands, &map, &sprite_assets);
+    commands.spawn((
+        OnEditorScreen,
+        EditorCursor,
+        Sprite::from_color(Color::srgba(1.0, 1.0, 1.0, 0.3), Vec2::splat(TILE_SIZE)),
+        Transform::from_translation(editor_tile_translation(&map, 0, 0).with_z(5.)),
+    ));
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(20.),
+                left: Val::Px(20.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.),
+                ..default()
+            },
+            OnEditorScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::Construction)),
+                locale.text_font(20.),
+            ));
+            for (i, tile) in EDITOR_PALETTE.iter().enumerate() {
+                parent.spawn((
+                    Text::new(format!("{} {}", i + 1, tile.name())),
+                    locale.text_font(16.),
+                    TextColor(Color::WHITE),
+                    EditorPaletteItem(i),
+                ));
+            }
+            parent.spawn((
+                Text::new(format!("\n{}", locale.tr(TextKey::EditorHelp))),
+                locale.text_font(14.),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+            parent.spawn((
+                Text::default(),
+                locale.text_font(14.),
+                TextColor(MENU_SELECTED_COLOR),
+                EditorStatusText,
+            ));
+        });
+}
+
+// 场地边界和编辑的地图一致，地图尺寸变化时重新生成
+fn spawn_editor_tiles(commands: &mut Commands, map: &LevelMap, sprite_assets: &SpriteAssets) {
+    commands.insert_resource(LevelBounds::of_map(map));
+    for row in 0..map.height {
+        for col in 0..map.width {
+            commands.spawn((
+                OnEditorScreen,
+                EditorTile { col, row },
+                editor_tile_sprite(map.get(col, row), sprite_assets),
+                Transform::from_translation(editor_tile_translation(map, col, row)),
+            ));
+        }
+    }
+}
+
+// 格子在世界坐标中的位置
+fn editor_tile_translation(map: &LevelMap, col: i32, row: i32) -> Vec3 {
+    tile_local_translation(map, col, row) + LevelBounds::of_map(map).offset()
+}
+
+fn editor_tile_sprite(tile: MapTile, sprite_assets: &SpriteAssets) -> Sprite {
+    let (image, layout, index) = match tile {
+        MapTile::Empty => {
+            return Sprite::from_color(EDITOR_EMPTY_TILE_COLOR, Vec2::splat(TILE_SIZE - 2.));
+        }
+        MapTile::Player1 => (&sprite_assets.player1, &sprite_assets.tank_layout, 0),
+        MapTile::Player2 => (&sprite_assets.player2, &sprite_assets.tank_layout, 0),
+        MapTile::Enemies => (&sprite_assets.enemies, &sprite_assets.enemies_layout, 0),
+        _ => (
+            &sprite_assets.map,
+            &sprite_assets.map_layout,
+            tile.map_sprite_index().unwrap(),
+        ),
+    };
+    Sprite {
+        image: image.clone(),
+        texture_atlas: Some(TextureAtlas {
+            layout: layout.clone(),
+            index,
+        }),
+        ..default()
+    }
+}
+
+pub fn editor_keyboard_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut multiplayer_mode: ResMut<MultiplayerMode>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+) {
+    let mut cursor = editor_level.cursor;
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        cursor.y -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        cursor.y += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        cursor.x -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        cursor.x += 1;
+    }
+    if editor_level.map.in_bounds(cursor.x, cursor.y) && cursor != editor_level.cursor {
+        editor_level.cursor = cursor;
+    }
+
+    // 选择调色板
+    let digit_keys = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    for (i, key) in digit_keys.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            editor_level.selected = i;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        editor_level.selected = (editor_level.selected + 1) % EDITOR_PALETTE.len();
+    }
+
+    // 绘制
+    let IVec2 { x: col, y: row } = editor_level.cursor;
+    if keyboard_input.pressed(KeyCode::Space) {
+        let tile = EDITOR_PALETTE[editor_level.selected];
+        if editor_level.map.get(col, row) != tile {
+            editor_level.map.set(col, row, tile);
+        }
+    }
+    if keyboard_input.any_pressed([KeyCode::Delete, KeyCode::Backspace])
+        && editor_level.map.get(col, row) != MapTile::Empty
+    {
+        editor_level.map.set(col, row, MapTile::Empty);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        *multiplayer_mode = if *multiplayer_mode == MultiplayerMode::SinglePlayer {
+            MultiplayerMode::TwoPlayers
+        } else {
+            MultiplayerMode::SinglePlayer
+        };
+        let key = match *multiplayer_mode {
+            MultiplayerMode::SinglePlayer => TextKey::EditorTestPlayOnePlayer,
+            MultiplayerMode::TwoPlayers => TextKey::EditorTestPlayTwoPlayers,
+        };
+        editor_level.status = (key, String::new());
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        let map = &editor_level.map;
+        editor_level.map = LevelMap::with_base(map.width, map.height);
+        editor_level.status = (TextKey::EditorNewLevel, String::new());
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        let seed = rand::random::<u64>();
+        let map = &editor_level.map;
+        editor_level.status = match generate_level(seed, map.width, map.height) {
+            Ok(map) => {
+                editor_level.map = map;
+                (TextKey::EditorGenerated, seed.to_string())
+            }
+            Err(e) => (TextKey::EditorGenerateFailed, e),
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyS) {
+        editor_level.status = match editor_level.map.save_stored(CUSTOM_LEVEL_FILE) {
+            Ok(()) => (TextKey::EditorSaved, CUSTOM_LEVEL_FILE.to_string()),
+            Err(e) => (TextKey::EditorSaveFailed, e),
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        match LevelMap::load_stored(CUSTOM_LEVEL_FILE) {
+            Ok(map) => {
+                editor_level.map = map;
+                editor_level.cursor = IVec2::ZERO;
+                editor_level.status = (TextKey::EditorLoaded, CUSTOM_LEVEL_FILE.to_string());
+            }
+            Err(e) => editor_level.status = (TextKey::EditorLoadFailed, e),
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        let map = &editor_level.map;
+        if map.positions(MapTile::Home).is_empty() {
+            editor_level.status = (TextKey::EditorNeedsHome, String::new());
+        } else if map.positions(MapTile::Player1).is_empty() {
+            editor_level.status = (TextKey::EditorNeedsPlayer1, String::new());
+        } else if map.positions(MapTile::Enemies).is_empty() {
+            editor_level.status = (TextKey::EditorNeedsEnemies, String::new());
+        } else {
+            info!("Editor test play");
+            commands.insert_resource(CustomLevel(Some(map.clone())));
+            commands.insert_resource(EditorTestPlay(true));
+            app_state.set(AppState::Playing);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_state.set(AppState::StartMenu);
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Tab, KeyCode::KeyM])
+        || digit_keys
+            .iter()
+            .any(|key| keyboard_input.just_pressed(*key))
+    {
+        sound_ew.send(SoundEvent(Sound::ModeSwitch));
+    }
+}
+
+pub fn editor_mouse_input(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    q_window: Single<&Window, With<PrimaryWindow>>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<GameCamera>>,
+    mut editor_level: ResMut<EditorLevel>,
+) {
+    let (camera, camera_transform) = *q_camera;
+    // 窗口坐标减去黑边得到视口坐标
+    let viewport_min = camera
+        .logical_viewport_rect()
+        .map_or(Vec2::ZERO, |rect| rect.min);
+    let Some(world_pos) = q_window.cursor_position().and_then(|pos| {
+        camera
+            .viewport_to_world_2d(camera_transform, pos - viewport_min)
+            .ok()
+    }) else {
+        return;
+    };
+    let local = world_pos - LevelBounds::of_map(&editor_level.map).offset().truncate();
+    let col = (local.x / TILE_SIZE).floor() as i32;
+    let row = editor_level.map.height - 1 - (local.y / TILE_SIZE).floor() as i32;
+    if !editor_level.map.in_bounds(col, row) {
+        return;
+    }
+    if editor_level.cursor != IVec2::new(col, row) {
+        editor_level.cursor = IVec2::new(col, row);
+    }
+    let tile = if mouse_input.pressed(MouseButton::Left) {
+        EDITOR_PALETTE[editor_level.selected]
+    } else if mouse_input.pressed(MouseButton::Right) {
+        MapTile::Empty
+    } else {
+        return;
+    };
+    if editor_level.map.get(col, row) != tile {
+        editor_level.map.set(col, row, tile);
+    }
+}
+
+pub fn update_editor_screen(
+    mut commands: Commands,
+    editor_level: Res<EditorLevel>,
+    level_bounds: Res<LevelBounds>,
+    sprite_assets: Res<SpriteAssets>,
+    locale: Res<Locale>,
+    mut q_tiles: Query<(Entity, &EditorTile, &mut Sprite)>,
+    mut q_cursor: Query<&mut Transform, With<EditorCursor>>,
+    mut q_palette_items: Query<(&EditorPaletteItem, &mut TextColor)>,
+    mut q_status_text: Query<&mut Text, With<EditorStatusText>>,
+) {
+    if !editor_level.is_changed() {
+        return;
+    }
+    let map = &editor_level.map;
+    if *level_bounds != LevelBounds::of_map(map) {
+        // 读取了不同尺寸的地图
+        for (entity, _, _) in &q_tiles {
+            commands.entity(entity).despawn();
+        }
+        spawn_editor_tiles(&mut commands, map, &sprite_assets);
+    } else {
+        for (_, tile, mut sprite) in &mut q_tiles {
+            *sprite = editor_tile_sprite(map.get(tile.col, tile.row), &sprite_assets);
+        }
+    }
+    for mut transform in &mut q_cursor {
+        transform.translation =
+            editor_tile_translation(map, editor_level.cursor.x, editor_level.cursor.y).with_z(5.);
+    }
+    for (palette_item, mut text_color) in &mut q_palette_items {
+        text_color.0 = if palette_item.0 == editor_level.selected {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+    let (key, detail) = &editor_level.status;
+    let status = if detail.is_empty() {
+        locale.tr(*key).to_string()
+    } else {
+        format!("{} {}", locale.tr(*key), detail)
+    };
+    for mut text in &mut q_status_text {
+        text.0.clone_from(&status);
+    }
+}
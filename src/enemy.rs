@@ -9,8 +9,11 @@ use crate::{
         ENEMY_REFRESH_BULLET_INTERVAL, ENEMY_SPEED, MAX_LIVE_ENEMIES, TANK_SCALE, TANK_SIZE,
         TILE_SIZE,
     },
-    level::{EnemiesMarker, LevelItem},
+    event_log::{GameEvent, Shooter},
+    level::EnemiesMarker,
     player::PlayerNo,
+    sprites::SpriteAssets,
+    tile_grid::TileGrid,
 };
 
 // 当前关卡生成的敌人数量
@@ -20,6 +23,58 @@ pub struct LevelSpawnedEnemies(pub i32);
 #[derive(Component)]
 pub struct Enemy;
 
+// 敌人类型，生存模式中随波次混合出现，普通关卡只有普通坦克
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnemyKind {
+    #[default]
+    Basic,
+    // 移动快
+    Fast,
+    // 射击快
+    Power,
+    // 需要多次击中
+    Armor,
+}
+
+impl EnemyKind {
+    pub fn speed(self) -> f32 {
+        match self {
+            EnemyKind::Fast => ENEMY_SPEED * 1.6,
+            EnemyKind::Armor => ENEMY_SPEED * 0.8,
+            EnemyKind::Basic | EnemyKind::Power => ENEMY_SPEED,
+        }
+    }
+
+    pub fn refresh_bullet_interval(self) -> f32 {
+        match self {
+            EnemyKind::Power => ENEMY_REFRESH_BULLET_INTERVAL / 2.,
+            _ => ENEMY_REFRESH_BULLET_INTERVAL,
+        }
+    }
+
+    // 击毁需要击中的次数
+    pub fn hits(self) -> u32 {
+        match self {
+            EnemyKind::Armor => 4,
+            _ => 1,
+        }
+    }
+
+    // 可使用的颜色，对应enemies_sprite_index_sets的下标
+    fn sprite_sets(self) -> std::ops::Range<usize> {
+        match self {
+            EnemyKind::Basic => 0..4,
+            EnemyKind::Fast => 4..6,
+            EnemyKind::Power => 6..7,
+            EnemyKind::Armor => 7..8,
+        }
+    }
+}
+
+// 敌人被击毁前还需要被击中的次数
+#[derive(Component)]
+pub struct EnemyArmor(pub u32);
+
 // 转向计时器
 #[derive(Component)]
 pub struct EnemyChangeDirectionTimer(pub Timer);
@@ -30,8 +85,7 @@ pub fn auto_spawn_enemies(
     q_enemies: Query<&Transform, With<Enemy>>,
     q_enemies_marker: Query<&GlobalTransform, With<EnemiesMarker>>,
     q_players: Query<&Transform, With<PlayerNo>>,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: Res<SpriteAssets>,
 ) {
     if q_enemies.into_iter().len() >= MAX_LIVE_ENEMIES as usize {
         // 战场上存活敌人已达到最大值
@@ -41,72 +95,68 @@ pub fn auto_spawn_enemies(
         // 本关卡已生成敌人数量达最大值
         return;
     }
-    let mut marker_positions = Vec::new();
-    for enemy_marker in &q_enemies_marker {
-        // 防止player1_marker还未初始化
-        if enemy_marker.translation() == Vec3::ZERO {
-            continue;
-        }
-        marker_positions.push(enemy_marker.clone());
+    let marker_positions: Vec<Vec3> = q_enemies_marker
+        .iter()
+        .map(GlobalTransform::translation)
+        .collect();
+    let tanks: Vec<Vec3> = q_enemies
+        .iter()
+        .chain(&q_players)
+        .map(|transform| transform.translation)
+        .collect();
+    if let Some(pos) = choose_enemy_spawn(&marker_positions, &tanks) {
+        spawn_enemy(pos, EnemyKind::Basic, &mut commands, &sprite_assets);
+        level_spawned_enemies.0 += 1;
     }
+}
 
-    if marker_positions.len() > 0 {
-        // 随机地点
-        let mut rng = rand::thread_rng();
-        let choosed_pos = marker_positions
-            .get(rng.gen_range(0..marker_positions.len()))
-            .unwrap()
-            .translation();
+// 随机选择一个敌人出生点，离场上坦克过近时本次不生成
+pub fn choose_enemy_spawn(marker_positions: &[Vec3], tanks: &[Vec3]) -> Option<Vec3> {
+    // 防止enemies_marker还未初始化
+    let marker_positions: Vec<Vec3> = marker_positions
+        .iter()
+        .copied()
+        .filter(|pos| *pos != Vec3::ZERO)
+        .collect();
+    if marker_positions.is_empty() {
+        return None;
+    }
+    // 随机地点
+    let mut rng = rand::thread_rng();
+    let choosed_pos = marker_positions[rng.gen_range(0..marker_positions.len())];
 
-        // 不能距离战场坦克过近
-        for enemy_pos in &q_enemies {
-            if choosed_pos.distance(enemy_pos.translation) < 2. * TILE_SIZE {
-                return;
-            }
-        }
-        for player_pos in &q_players {
-            if choosed_pos.distance(player_pos.translation) < 2. * TILE_SIZE {
-                return;
-            }
-        }
-        spawn_enemy(
-            choosed_pos,
-            &mut commands,
-            &asset_server,
-            &mut atlas_layouts,
-        );
-        level_spawned_enemies.0 += 1;
+    // 不能距离战场坦克过近
+    if tanks
+        .iter()
+        .any(|tank| choosed_pos.distance(*tank) < 2. * TILE_SIZE)
+    {
+        return None;
     }
+    Some(choosed_pos)
 }
 
 pub fn spawn_enemy(
     pos: Vec3,
+    kind: EnemyKind,
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: &SpriteAssets,
 ) {
-    let enemies_texture_handle = asset_server.load("textures/enemies.bmp");
-    let enemies_texture_atlas =
-        TextureAtlasLayout::from_grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 8, None, None);
-    let enemies_atlas_layout_handle = atlas_layouts.add(enemies_texture_atlas);
-
-    // 随机颜色
-    let indexes: Vec<i32> = enemies_sprite_index_sets()
+    // 在该类型的颜色中随机
+    let indexes: Vec<i32> = enemies_sprite_index_sets()[kind.sprite_sets()]
         .iter()
-        .map(|v| *v.get(0).unwrap())
+        .map(|v| *v.first().unwrap())
         .collect();
     let mut rng = rand::thread_rng();
-    let choosed_index = indexes
-        .get(rng.gen_range(0..indexes.len()))
-        .unwrap()
-        .clone();
+    let choosed_index = *indexes.get(rng.gen_range(0..indexes.len())).unwrap();
 
     commands.spawn((
         Enemy,
+        kind,
+        EnemyArmor(kind.hits()),
         Sprite {
-            image: enemies_texture_handle,
+            image: sprite_assets.enemies.clone(),
             texture_atlas: Some(TextureAtlas {
-                layout: enemies_atlas_layout_handle,
+                layout: sprite_assets.enemies_layout.clone(),
                 index: choosed_index as usize,
             }),
             ..default()
@@ -117,7 +167,7 @@ pub fn spawn_enemy(
             ..default()
         },
         TankRefreshBulletTimer(Timer::from_seconds(
-            ENEMY_REFRESH_BULLET_INTERVAL,
+            kind.refresh_bullet_interval(),
             TimerMode::Repeating,
         )),
         EnemyChangeDirectionTimer(Timer::from_seconds(1.0, TimerMode::Once)),
@@ -147,72 +197,54 @@ pub fn enemies_move(
             &mut Sprite,
             &mut AnimationIndices,
             &mut EnemyChangeDirectionTimer,
+            &EnemyKind,
         ),
         With<Enemy>,
     >,
-    q_level_items: Query<(&LevelItem, &GlobalTransform)>,
+    tile_grid: Res<TileGrid>,
     time: Res<Time>,
 ) {
-    for (mut transform, mut direction, mut sprite, mut indices, mut timer) in &mut q_enemies {
+    for (mut transform, mut direction, mut sprite, mut indices, mut timer, kind) in &mut q_enemies {
         timer.0.tick(time.delta());
         if !timer.0.finished() {
+            let speed = kind.speed();
             match *direction {
                 common::Direction::Up => {
-                    transform.translation.y += ENEMY_SPEED * time.delta_secs();
+                    transform.translation.y += speed * time.delta_secs();
                 }
                 common::Direction::Right => {
-                    transform.translation.x += ENEMY_SPEED * time.delta_secs();
+                    transform.translation.x += speed * time.delta_secs();
                 }
                 common::Direction::Down => {
-                    transform.translation.y -= ENEMY_SPEED * time.delta_secs();
+                    transform.translation.y -= speed * time.delta_secs();
                 }
                 common::Direction::Left => {
-                    transform.translation.x -= ENEMY_SPEED * time.delta_secs();
+                    transform.translation.x -= speed * time.delta_secs();
                 }
             }
             continue;
         }
 
         // 重新选择方向
-        let mut can_left = true;
-        let mut can_right = true;
-        let mut can_up = true;
-        let mut can_down = true;
-
         // 当前可走路径
-        for (level_item, level_item_transform) in &q_level_items {
-            if *level_item == LevelItem::Tree {
-                continue;
-            }
-            if (level_item_transform.translation().x - transform.translation.x).abs()
-                < (TANK_SIZE as f32 + TILE_SIZE) / 2.0 - 5.0
-            {
-                if level_item_transform.translation().y > transform.translation.y
-                    && level_item_transform.translation().y - transform.translation.y < TILE_SIZE
-                {
-                    can_up = false;
-                }
-                if level_item_transform.translation().y < transform.translation.y
-                    && transform.translation.y - level_item_transform.translation().y < TILE_SIZE
-                {
-                    can_down = false;
-                }
-            }
-            if (level_item_transform.translation().y - transform.translation.y).abs()
-                < (TANK_SIZE as f32 + TILE_SIZE) / 2. - 5.0
-            {
-                if level_item_transform.translation().x > transform.translation.x
-                    && level_item_transform.translation().x - transform.translation.x < TILE_SIZE
-                {
-                    can_right = false;
-                }
-                if level_item_transform.translation().x < transform.translation.x
-                    && transform.translation.x - level_item_transform.translation().x < TILE_SIZE
-                {
-                    can_left = false;
-                }
-            }
-        }
+        let pos = transform.translation.truncate();
+        let reach = (TANK_SIZE as f32 + TILE_SIZE) / 2.0 - 5.0;
+        let can_up = !tile_grid.is_blocked(
+            pos + Vec2::new(-reach, 0.),
+            pos + Vec2::new(reach, TILE_SIZE),
+        );
+        let can_down = !tile_grid.is_blocked(
+            pos + Vec2::new(-reach, -TILE_SIZE),
+            pos + Vec2::new(reach, 0.),
+        );
+        let can_right = !tile_grid.is_blocked(
+            pos + Vec2::new(0., -reach),
+            pos + Vec2::new(TILE_SIZE, reach),
+        );
+        let can_left = !tile_grid.is_blocked(
+            pos + Vec2::new(-TILE_SIZE, -reach),
+            pos + Vec2::new(0., reach),
+        );
         if !can_left && !can_right && !can_up && !can_down {
             continue;
         }
@@ -222,26 +254,10 @@ pub fn enemies_move(
         let choosed_direction = loop {
             let rand = rng.gen_range(0..9);
             match rand {
-                0 => {
-                    if can_up {
-                        break common::Direction::Up;
-                    }
-                }
-                1 | 2 => {
-                    if can_left {
-                        break common::Direction::Left;
-                    }
-                }
-                3 | 4 => {
-                    if can_right {
-                        break common::Direction::Right;
-                    }
-                }
-                5 | 6 | 7 | 8 => {
-                    if can_down {
-                        break common::Direction::Down;
-                    }
-                }
+                0 if can_up => break common::Direction::Up,
+                1 | 2 if can_left => break common::Direction::Left,
+                3 | 4 if can_right => break common::Direction::Right,
+                5..=8 if can_down => break common::Direction::Down,
                 _ => {}
             }
         };
@@ -268,20 +284,25 @@ pub fn enemies_attack(
     >,
     time: Res<Time>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut game_event_ew: EventWriter<GameEvent>,
+    sprite_assets: Res<SpriteAssets>,
 ) {
     for (transform, direction, mut refresh_bullet_timer) in &mut q_players {
         refresh_bullet_timer.tick(time.delta());
         if refresh_bullet_timer.just_finished() {
             spawn_bullet(
                 &mut commands,
-                &asset_server,
-                &mut atlas_layouts,
+                &sprite_assets,
                 Bullet::Enemy,
                 transform.translation,
-                direction.clone(),
+                *direction,
             );
+            game_event_ew.send(GameEvent::ShotFired {
+                shooter: Shooter::Enemy,
+                x: transform.translation.x,
+                y: transform.translation.y,
+                direction: *direction,
+            });
         }
     }
 }
@@ -360,7 +381,7 @@ pub fn new_sprite_index(current_index: i32, direction: common::Direction) -> i32
             info!("found index_set");
             match direction {
                 common::Direction::Up => {
-                    return *index_set.get(0).unwrap();
+                    return *index_set.first().unwrap();
                 }
                 common::Direction::Right => {
                     return *index_set.get(1).unwrap();
@@ -374,5 +395,5 @@ pub fn new_sprite_index(current_index: i32, direction: common::Direction) -> i32
             }
         }
     }
-    return 0;
+    0
 }
@@ -0,0 +1,248 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::Serialize;
+
+use crate::bullet::{Bullet, BulletOwner};
+use crate::common::{AppState, Direction, MultiplayerMode};
+use crate::level::stage_number;
+use crate::map::{CustomLevel, LevelPack};
+use crate::player::{PlayerLives, PlayerNo, PlayerScores};
+
+// 对局事件日志，每行一条JSON（JSON Lines），用于分析难度、死亡位置热力图和对比平衡性改动
+
+// 开火、击中或击毁的一方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shooter {
+    Player1,
+    Player2,
+    Enemy,
+}
+
+impl Shooter {
+    pub fn player(player_no: PlayerNo) -> Self {
+        if player_no.0 == 2 {
+            Shooter::Player2
+        } else {
+            Shooter::Player1
+        }
+    }
+
+    pub fn of_bullet(bullet: &Bullet, bullet_owner: Option<&BulletOwner>) -> Self {
+        match (bullet, bullet_owner) {
+            (Bullet::Player, Some(owner)) => Shooter::player(owner.0),
+            (Bullet::Player, None) => Shooter::Player1,
+            (Bullet::Enemy, _) => Shooter::Enemy,
+        }
+    }
+}
+
+// 子弹击中的物体
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitTarget {
+    Player1,
+    Player2,
+    Enemy,
+    StoneWall,
+    IronWall,
+    Home,
+    // 场地边界
+    Boundary,
+}
+
+impl HitTarget {
+    pub fn player(player_no: PlayerNo) -> Self {
+        if player_no.0 == 2 {
+            HitTarget::Player2
+        } else {
+            HitTarget::Player1
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageResult {
+    Clear,
+    GameOver,
+    Restart,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerUpKind {
+    // 出生时自带的护盾
+    SpawnShield,
+    Shield,
+    ExtraLife,
+}
+
+// 坐标为世界坐标
+#[derive(Debug, Clone, PartialEq, Event, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    ShotFired {
+        shooter: Shooter,
+        x: f32,
+        y: f32,
+        direction: Direction,
+    },
+    // 包括被护盾挡住的子弹
+    Hit {
+        shooter: Shooter,
+        target: HitTarget,
+        x: f32,
+        y: f32,
+    },
+    // 玩家击毁敌人
+    Kill {
+        killer: Shooter,
+        x: f32,
+        y: f32,
+    },
+    WallDestroyed {
+        shooter: Shooter,
+        x: f32,
+        y: f32,
+    },
+    PlayerDeath {
+        player: u32,
+        killer: Shooter,
+        lives_left: i8,
+        x: f32,
+        y: f32,
+    },
+    PowerUp {
+        player: u32,
+        kind: PowerUpKind,
+    },
+    StageStart {
+        players: u32,
+    },
+    StageEnd {
+        result: StageResult,
+        scores: [u32; 2],
+        lives: [i8; 2],
+    },
+}
+
+// 对局进行中的帧数，暂停、菜单和结算界面不计数，作为事件日志的时间戳
+#[derive(Debug, Resource, Default)]
+pub struct GameTick(pub u32);
+
+pub fn advance_game_tick(mut game_tick: ResMut<GameTick>) {
+    game_tick.0 += 1;
+}
+
+#[derive(Serialize)]
+struct EventRecord<'a> {
+    tick: u32,
+    stage: Option<usize>,
+    #[serde(flatten)]
+    event: &'a GameEvent,
+}
+
+#[derive(Debug, Resource, Default)]
+pub struct GameEventLog {
+    writer: Option<BufWriter<File>>,
+    // 进行中的关卡，从暂停恢复时不算新关卡。关卡结束事件写入后清除
+    stage: Option<usize>,
+}
+
+impl GameEventLog {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(GameEventLog {
+            writer: Some(BufWriter::new(file)),
+            stage: None,
+        })
+    }
+
+    fn write(&mut self, tick: u32, event: &GameEvent) -> Result<(), String> {
+        let record = EventRecord {
+            tick,
+            stage: self.stage,
+            event,
+        };
+        if matches!(event, GameEvent::StageEnd { .. }) {
+            self.stage = None;
+        }
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| e.to_string())
+    }
+}
+
+pub fn log_stage_start(
+    mut game_event_log: ResMut<GameEventLog>,
+    mut game_event_ew: EventWriter<GameEvent>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    level_pack: Res<LevelPack>,
+) {
+    if game_event_log.stage.is_some() {
+        return;
+    }
+    game_event_log.stage = Some(stage_number(&level_selection, &custom_level, &level_pack));
+    game_event_ew.send(GameEvent::StageStart {
+        players: match *multiplayer_mode {
+            MultiplayerMode::SinglePlayer => 1,
+            MultiplayerMode::TwoPlayers => 2,
+        },
+    });
+}
+
+// 根据进入的状态区分关卡结束的原因
+pub fn log_stage_end(
+    game_event_log: Res<GameEventLog>,
+    mut game_event_ew: EventWriter<GameEvent>,
+    app_state: Res<State<AppState>>,
+    player_scores: Res<PlayerScores>,
+    player_lives: Res<PlayerLives>,
+) {
+    if game_event_log.stage.is_none() {
+        return;
+    }
+    game_event_ew.send(GameEvent::StageEnd {
+        result: match app_state.get() {
+            AppState::StageClear => StageResult::Clear,
+            AppState::GameOver => StageResult::GameOver,
+            AppState::Restarting => StageResult::Restart,
+            _ => StageResult::Quit,
+        },
+        scores: [player_scores.player1, player_scores.player2],
+        lives: [player_lives.player1, player_lives.player2],
+    });
+}
+
+pub fn write_game_events(
+    mut game_event_log: ResMut<GameEventLog>,
+    mut game_event_er: EventReader<GameEvent>,
+    game_tick: Res<GameTick>,
+) {
+    let mut written = false;
+    for event in game_event_er.read() {
+        if let Err(e) = game_event_log.write(game_tick.0, event) {
+            warn!("Failed to write event log, logging stopped: {}", e);
+            game_event_log.writer = None;
+        }
+        written = true;
+    }
+    if !written {
+        return;
+    }
+    if let Some(writer) = &mut game_event_log.writer {
+        if let Err(e) = writer.flush() {
+            warn!("Failed to flush event log, logging stopped: {}", e);
+            game_event_log.writer = None;
+        }
+    }
+}
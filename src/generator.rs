@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::map::{LevelMap, MapTile};
+
+// 生成失败时的最大重试次数
+const MAX_GENERATE_ATTEMPTS: u32 = 100;
+// 家两侧各清空3格，顶部两行留给敌人出生，至少还要一行放地形和底部两行的家
+pub const MIN_GENERATED_WIDTH: i32 = 7;
+pub const MIN_GENERATED_HEIGHT: i32 = 5;
+
+// 当前随机关卡的种子，通关后种子加一生成下一关
+#[derive(Debug, Resource, Default)]
+pub struct GeneratedLevel(pub Option<u64>);
+
+// 根据种子生成左右对称的随机关卡，保证所有出生点都能到达家
+pub fn generate_level(seed: u64, width: i32, height: i32) -> Result<LevelMap, String> {
+    if width < MIN_GENERATED_WIDTH || height < MIN_GENERATED_HEIGHT {
+        return Err(format!(
+            "level size {}x{} is too small, need at least {}x{}",
+            width, height, MIN_GENERATED_WIDTH, MIN_GENERATED_HEIGHT
+        ));
+    }
+    // 家只占一格，宽度为偶数时无法以家为中心左右对称
+    if width % 2 == 0 {
+        return Err(format!(
+            "level width {} must be odd to mirror around the home",
+            width
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..MAX_GENERATE_ATTEMPTS {
+        let map = generate_candidate(&mut rng, width, height);
+        if map.unreachable_spawns().is_empty() {
+            return Ok(map);
+        }
+    }
+    warn!(
+        "Failed to generate level with seed {}, use empty level",
+        seed
+    );
+    Ok(LevelMap::with_base(width, height))
+}
+
+fn generate_candidate(rng: &mut StdRng, width: i32, height: i32) -> LevelMap {
+    let mut map = LevelMap::new(width, height);
+    let half = (width + 1) / 2;
+
+    // 在左半边放置地形块，再镜像到右半边
+    let features = rng.gen_range(12..20);
+    for _ in 0..features {
+        let tile = match rng.gen_range(0..10) {
+            0..=4 => MapTile::StoneWall,
+            5 | 6 => MapTile::IronWall,
+            7 => MapTile::Water,
+            _ => MapTile::Tree,
+        };
+        let (w, h) = if rng.gen_bool(0.5) {
+            (rng.gen_range(1..=2), rng.gen_range(2..=5))
+        } else {
+            (rng.gen_range(2..=5), rng.gen_range(1..=2))
+        };
+        let col = rng.gen_range(0..half);
+        // 顶部两行留给敌人出生
+        let row = rng.gen_range(2..height - 1);
+        for r in row..(row + h).min(height) {
+            for c in col..(col + w).min(half) {
+                map.set(c, r, tile);
+                map.set(width - 1 - c, r, tile);
+            }
+        }
+    }
+
+    // 清空家附近区域后放置家、砖墙和玩家出生点
+    let home_col = width / 2;
+    let bottom = height - 1;
+    for row in bottom - 2..=bottom {
+        for col in home_col - 3..=home_col + 3 {
+            map.set(col, row, MapTile::Empty);
+        }
+    }
+    let base = LevelMap::with_base(width, height);
+    for row in 0..height {
+        for col in 0..width {
+            let tile = base.get(col, row);
+            if tile != MapTile::Empty {
+                map.set(col, row, tile);
+            }
+        }
+    }
+    // 敌人出生点下方保持畅通
+    for spawn in map.positions(MapTile::Enemies) {
+        map.set(spawn.x, spawn.y + 1, MapTile::Empty);
+    }
+    map
+}
+
+pub fn reset_generated_level(mut generated_level: ResMut<GeneratedLevel>) {
+    generated_level.0 = None;
+}
@@ -0,0 +1,458 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{Sound, SoundEvent};
+use crate::common::{AppState, Difficulty, MultiplayerMode};
+use crate::debug::{CheatsUsed, GodMode};
+use crate::editor::EditorTestPlay;
+use crate::generator::GeneratedLevel;
+use crate::level::stage_number;
+use crate::locale::{Locale, TextKey};
+use crate::map::{CustomLevel, LevelPack};
+use crate::player::PlayerScores;
+use crate::storage;
+use crate::survival::{format_survival_time, SurvivalMode, SurvivalWaves};
+use crate::ui::MENU_SELECTED_COLOR;
+
+pub const HIGH_SCORE_FILE: &str = "high_scores.json";
+pub const SURVIVAL_HIGH_SCORE_FILE: &str = "survival_high_scores.json";
+pub const MAX_HIGH_SCORES: usize = 10;
+const INITIALS_LEN: usize = 3;
+
+#[derive(Component)]
+pub struct OnHighScoresScreen;
+
+// 游戏结束时输入名字缩写的界面
+#[derive(Component)]
+pub struct OnInitialsEntryScreen;
+
+#[derive(Component)]
+pub struct InitialsLetter(pub usize);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub score: u32,
+    pub stage: usize,
+    pub mode: String,
+    pub difficulty: String,
+    // 生存模式坚持的时间（秒），此时stage为到达的波次
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub survival_secs: Option<u32>,
+}
+
+impl HighScoreEntry {
+    // 生存模式按波次和时间排名，其他模式按得分排名
+    fn rank_key(&self) -> (u32, u32) {
+        match self.survival_secs {
+            Some(secs) => (self.stage as u32, secs),
+            None => (self.score, 0),
+        }
+    }
+}
+
+// 按得分从高到低排序的最高分榜
+#[derive(Debug, Resource, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    pub fn load(name: &str) -> Self {
+        let Some(text) = storage::load_text(name) else {
+            return HighScores::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            warn!("Failed to parse {}: {}", name, e);
+            HighScores::default()
+        })
+    }
+
+    pub fn save(&self, name: &str) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| storage::save_text(name, &text));
+        if let Err(e) = result {
+            warn!("Failed to save {}: {}", name, e);
+        }
+    }
+
+    pub fn qualifies(&self, entry: &HighScoreEntry) -> bool {
+        let key = entry.rank_key();
+        key > (0, 0)
+            && (self.entries.len() < MAX_HIGH_SCORES
+                || self
+                    .entries
+                    .last()
+                    .is_some_and(|last| key > last.rank_key()))
+    }
+
+    // 同分时先上榜的排在前面，返回插入位置
+    pub fn insert(&mut self, entry: HighScoreEntry) -> usize {
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| entry.rank_key() > e.rank_key())
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        rank
+    }
+}
+
+// 生存模式单独的排行榜
+#[derive(Debug, Resource, Default)]
+pub struct SurvivalHighScores(pub HighScores);
+
+// 排行榜界面当前显示的榜单
+#[derive(Debug, Resource, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HighScoreBoard {
+    #[default]
+    Classic,
+    Survival,
+}
+
+// 等待输入名字的上榜成绩
+#[derive(Debug, Resource, Default)]
+pub struct PendingHighScore(pub Option<HighScoreEntry>);
+
+// 名字缩写输入状态，保留上次输入的名字
+#[derive(Debug, Resource)]
+pub struct InitialsEntry {
+    pub letters: [u8; INITIALS_LEN],
+    pub cursor: usize,
+}
+
+impl Default for InitialsEntry {
+    fn default() -> Self {
+        InitialsEntry {
+            letters: [b'A'; INITIALS_LEN],
+            cursor: 0,
+        }
+    }
+}
+
+impl InitialsEntry {
+    pub fn text(&self) -> String {
+        self.letters.iter().map(|letter| *letter as char).collect()
+    }
+}
+
+// 游戏结束时检查是否上榜，编辑器试玩、随机关卡和使用过作弊命令的对局不计入，生存模式记入生存模式排行榜
+pub fn check_high_score(
+    high_scores: Res<HighScores>,
+    survival_high_scores: Res<SurvivalHighScores>,
+    mut pending_high_score: ResMut<PendingHighScore>,
+    player_scores: Res<PlayerScores>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    level_pack: Res<LevelPack>,
+    generated_level: Res<GeneratedLevel>,
+    editor_test_play: Res<EditorTestPlay>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    difficulty: Res<Difficulty>,
+    survival_mode: Res<SurvivalMode>,
+    survival_waves: Res<SurvivalWaves>,
+    cheats_used: Res<CheatsUsed>,
+    god_mode: Res<GodMode>,
+) {
+    pending_high_score.0 = None;
+    if editor_test_play.0 || generated_level.0.is_some() {
+        return;
+    }
+    if cheats_used.0 || god_mode.0 {
+        info!("Cheats used, high score not recorded");
+        return;
+    }
+    let (stage, survival_secs, board) = if survival_mode.0 {
+        (
+            survival_waves.wave as usize,
+            Some(survival_waves.elapsed_secs as u32),
+            &survival_high_scores.0,
+        )
+    } else {
+        (
+            stage_number(&level_selection, &custom_level, &level_pack),
+            None,
+            &*high_scores,
+        )
+    };
+    let entry = HighScoreEntry {
+        initials: String::new(),
+        score: player_scores.player1 + player_scores.player2,
+        stage,
+        mode: multiplayer_mode.label().to_string(),
+        difficulty: difficulty.label().to_string(),
+        survival_secs,
+    };
+    if board.qualifies(&entry) {
+        pending_high_score.0 = Some(entry);
+    }
+}
+
+pub fn spawn_initials_entry(
+    commands: &mut Commands,
+    locale: &Locale,
+    entry: &HighScoreEntry,
+    initials_entry: &InitialsEntry,
+) {
+    let text_font = |font_size: f32| locale.text_font(font_size);
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(60.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
+                ..default()
+            },
+            OnInitialsEntryScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(format!(
+                    "{} {}",
+                    locale.tr(TextKey::NewHighScore),
+                    entry.score
+                )),
+                text_font(24.),
+                TextColor(MENU_SELECTED_COLOR),
+            ));
+            parent.spawn((Text::new(locale.tr(TextKey::EnterInitials)), text_font(20.)));
+            parent
+                .spawn(Node {
+                    column_gap: Val::Px(12.),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (i, letter) in initials_entry.letters.iter().enumerate() {
+                        parent.spawn((
+                            Text::new((*letter as char).to_string()),
+                            text_font(36.),
+                            TextColor(Color::WHITE),
+                            InitialsLetter(i),
+                        ));
+                    }
+                });
+            parent.spawn((Text::new(locale.tr(TextKey::InitialsHelp)), text_font(16.)));
+        });
+}
+
+pub fn initials_entry_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    q_initials_entry: Query<Entity, With<OnInitialsEntryScreen>>,
+    mut initials_entry: ResMut<InitialsEntry>,
+    mut pending_high_score: ResMut<PendingHighScore>,
+    mut high_scores: ResMut<HighScores>,
+    mut survival_high_scores: ResMut<SurvivalHighScores>,
+    mut sound_ew: EventWriter<SoundEvent>,
+) {
+    if q_initials_entry.is_empty() {
+        return;
+    }
+    let cursor = initials_entry.cursor;
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        let letter = &mut initials_entry.letters[cursor];
+        *letter = if *letter == b'Z' { b'A' } else { *letter + 1 };
+    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        let letter = &mut initials_entry.letters[cursor];
+        *letter = if *letter == b'A' { b'Z' } else { *letter - 1 };
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        initials_entry.cursor = (cursor + 1) % INITIALS_LEN;
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        initials_entry.cursor = (cursor + INITIALS_LEN - 1) % INITIALS_LEN;
+    } else if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        if let Some(mut entry) = pending_high_score.0.take() {
+            entry.initials = initials_entry.text();
+            let (board, file) = if entry.survival_secs.is_some() {
+                (&mut survival_high_scores.0, SURVIVAL_HIGH_SCORE_FILE)
+            } else {
+                (&mut *high_scores, HIGH_SCORE_FILE)
+            };
+            let rank = board.insert(entry);
+            info!("New high score in {}, rank={}", file, rank + 1);
+            board.save(file);
+        }
+        initials_entry.cursor = 0;
+        for entity in &q_initials_entry {
+            commands.entity(entity).despawn_recursive();
+        }
+    } else {
+        return;
+    }
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
+
+pub fn update_initials_entry_screen(
+    initials_entry: Res<InitialsEntry>,
+    mut q_letters: Query<(&InitialsLetter, &mut Text, &mut TextColor)>,
+) {
+    for (letter, mut text, mut text_color) in &mut q_letters {
+        text.0 = (initials_entry.letters[letter.0] as char).to_string();
+        text_color.0 = if letter.0 == initials_entry.cursor {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+pub fn setup_high_scores_screen(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    high_scores: Res<HighScores>,
+    survival_high_scores: Res<SurvivalHighScores>,
+    board: Res<HighScoreBoard>,
+) {
+    spawn_high_scores_screen(
+        &mut commands,
+        &locale,
+        &high_scores,
+        &survival_high_scores,
+        *board,
+    );
+}
+
+fn spawn_high_scores_screen(
+    commands: &mut Commands,
+    locale: &Locale,
+    high_scores: &HighScores,
+    survival_high_scores: &SurvivalHighScores,
+    board: HighScoreBoard,
+) {
+    let text_font = |font_size: f32| locale.text_font(font_size);
+    let (title, header, high_scores) = match board {
+        HighScoreBoard::Classic => (
+            TextKey::HighScores,
+            format!(
+                "{:<4} {:<4} {:>7} {:>5} {:>4} {:<10}",
+                locale.tr(TextKey::Rank),
+                locale.tr(TextKey::Name),
+                locale.tr(TextKey::Score),
+                locale.tr(TextKey::Stage),
+                locale.tr(TextKey::Mode),
+                locale.tr(TextKey::Difficulty)
+            ),
+            high_scores,
+        ),
+        HighScoreBoard::Survival => (
+            TextKey::SurvivalHighScores,
+            format!(
+                "{:<4} {:<4} {:>5} {:>6} {:>7} {:>4}",
+                locale.tr(TextKey::Rank),
+                locale.tr(TextKey::Name),
+                locale.tr(TextKey::Wave),
+                locale.tr(TextKey::Time),
+                locale.tr(TextKey::Score),
+                locale.tr(TextKey::Mode)
+            ),
+            &survival_high_scores.0,
+        ),
+    };
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.),
+                ..default()
+            },
+            OnHighScoresScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(title)),
+                text_font(32.),
+                Node {
+                    margin: UiRect::bottom(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                Text::new(header),
+                text_font(20.),
+                TextColor(MENU_SELECTED_COLOR),
+            ));
+            for (i, entry) in high_scores.entries.iter().enumerate() {
+                let line = match board {
+                    HighScoreBoard::Classic => format!(
+                        "{:<4} {:<4} {:>7} {:>5} {:>4} {:<10}",
+                        i + 1,
+                        entry.initials,
+                        entry.score,
+                        entry.stage,
+                        entry.mode,
+                        entry.difficulty
+                    ),
+                    HighScoreBoard::Survival => format!(
+                        "{:<4} {:<4} {:>5} {:>6} {:>7} {:>4}",
+                        i + 1,
+                        entry.initials,
+                        entry.stage,
+                        format_survival_time(entry.survival_secs.unwrap_or_default()),
+                        entry.score,
+                        entry.mode
+                    ),
+                };
+                parent.spawn((Text::new(line), text_font(20.)));
+            }
+            if high_scores.entries.is_empty() {
+                parent.spawn((Text::new(locale.tr(TextKey::NoRecords)), text_font(20.)));
+            }
+            parent.spawn((
+                Text::new(format!(
+                    "{}   {}",
+                    locale.tr(TextKey::SwitchBoardHelp),
+                    locale.tr(TextKey::PressEnterToReturn)
+                )),
+                text_font(16.),
+                Node {
+                    margin: UiRect::top(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+        });
+}
+
+// 左右切换普通和生存模式排行榜
+pub fn high_scores_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    locale: Res<Locale>,
+    high_scores: Res<HighScores>,
+    survival_high_scores: Res<SurvivalHighScores>,
+    mut board: ResMut<HighScoreBoard>,
+    q_screen: Query<Entity, With<OnHighScoresScreen>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space, KeyCode::Escape]) {
+        app_state.set(AppState::StartMenu);
+        return;
+    }
+    if !keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::ArrowRight]) {
+        return;
+    }
+    *board = match *board {
+        HighScoreBoard::Classic => HighScoreBoard::Survival,
+        HighScoreBoard::Survival => HighScoreBoard::Classic,
+    };
+    for entity in &q_screen {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_high_scores_screen(
+        &mut commands,
+        &locale,
+        &high_scores,
+        &survival_high_scores,
+        *board,
+    );
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
@@ -1,20 +1,65 @@
 use crate::{
+    cli::LaunchOptions,
     common::{
         AnimationIndices, AnimationTimer, AppState, HomeDyingEvent, ENEMIES_PER_LEVEL,
         LEVEL_COLUMNS, LEVEL_ROWS, MAX_LEVELS, SPRITE_TREE_ORDER, TILE_SIZE,
     },
+    editor::EditorTestPlay,
     enemy::{Enemy, LevelSpawnedEnemies},
+    generator::{generate_level, GeneratedLevel},
+    map::{spawn_level_map, CustomLevel, CustomLevelRoot, LevelMap, LevelPack},
     player::PlayerNo,
+    sprites::SpriteAssets,
 };
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-pub const LEVEL_TRANSLATION_OFFSET: Vec3 = Vec3::new(
-    -LEVEL_COLUMNS as f32 / 2.0 * TILE_SIZE,
-    -LEVEL_ROWS as f32 / 2. * TILE_SIZE,
-    0.0,
-);
+// 通关后等待胜利音乐播放完的时长
+const STAGE_CLEAR_SECS: f32 = 3.0;
+
+// 当前关卡的格子数，关卡以世界坐标原点为中心放置
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq)]
+pub struct LevelBounds {
+    pub columns: i32,
+    pub rows: i32,
+}
+
+impl Default for LevelBounds {
+    fn default() -> Self {
+        LevelBounds {
+            columns: LEVEL_COLUMNS,
+            rows: LEVEL_ROWS,
+        }
+    }
+}
+
+impl LevelBounds {
+    pub fn of_map(map: &LevelMap) -> Self {
+        LevelBounds {
+            columns: map.width,
+            rows: map.height,
+        }
+    }
+
+    // ldtk关卡的像素尺寸，不足一格按一格计算
+    pub fn from_px(width: i32, height: i32) -> Self {
+        let tiles = |px: i32| ((px + TILE_SIZE as i32 - 1) / TILE_SIZE as i32).max(1);
+        LevelBounds {
+            columns: tiles(width),
+            rows: tiles(height),
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.columns as f32, self.rows as f32) * TILE_SIZE
+    }
+
+    // 关卡左下角的世界坐标，关卡内坐标加上它得到世界坐标
+    pub fn offset(&self) -> Vec3 {
+        (-self.size() / 2.).extend(0.)
+    }
+}
 
 // 关卡地图元素
 #[derive(Component, Clone, PartialEq, Eq, Debug, Default)]
@@ -33,6 +78,15 @@ pub enum LevelItem {
     Home,
 }
 
+#[derive(Debug, Resource)]
+pub struct StageClearTimer(pub Timer);
+
+impl Default for StageClearTimer {
+    fn default() -> Self {
+        StageClearTimer(Timer::from_seconds(STAGE_CLEAR_SECS, TimerMode::Once))
+    }
+}
+
 // 关卡player1位置标记
 #[derive(Component, Default)]
 pub struct Player1Marker;
@@ -74,6 +128,8 @@ pub struct IronWallBundle {
     #[sprite_sheet("textures/map.bmp", 32, 32, 7, 1, 0, 0, 1)]
     sprite_sheet: Sprite,
 }
+// 树木由 spawn_ldtk_entity 单独生成，以控制sprite order
+#[allow(dead_code)]
 #[derive(Bundle, LdtkEntity, Default)]
 pub struct TreeBundle {
     #[from_entity_instance]
@@ -154,44 +210,96 @@ impl From<&EntityInstance> for LevelItem {
     }
 }
 
-pub fn setup_levels(
+// 关卡ldtk项目，启动时加载，关卡选择缩略图也从中读取
+#[derive(Debug, Resource)]
+pub struct LevelsProject(pub Handle<LdtkProject>);
+
+pub fn setup_levels_project(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    launch_options: Res<LaunchOptions>,
+) {
+    // 无渲染运行时没有ldtk插件，不加载ldtk文件
+    if launch_options.headless {
+        commands.insert_resource(LevelsProject(Handle::default()));
+        return;
+    }
+    let path = launch_options
+        .ldtk_asset_path()
+        .unwrap_or("levels.ldtk".to_string());
+    commands.insert_resource(LevelsProject(asset_server.load(path)));
+}
+
+pub fn setup_levels(
+    mut commands: Commands,
+    levels_project: Res<LevelsProject>,
+    sprite_assets: Res<SpriteAssets>,
     q_ldtk_world: Query<(), With<LdtkProjectHandle>>,
+    q_custom_level: Query<(), With<CustomLevelRoot>>,
+    custom_level: Res<CustomLevel>,
+    level_bounds: Res<LevelBounds>,
 ) {
-    if q_ldtk_world.iter().len() > 0 {
+    if q_ldtk_world.iter().len() > 0 || q_custom_level.iter().len() > 0 {
         // 从Paused状态进入时无需再load ldtk
         return;
     }
+    if let Some(map) = &custom_level.0 {
+        spawn_level_map(map, &mut commands, &sprite_assets);
+        return;
+    }
     commands.spawn(LdtkWorldBundle {
-        ldtk_handle: asset_server.load("levels.ldtk").into(),
-        transform: Transform::from_translation(Vec3::ZERO + LEVEL_TRANSLATION_OFFSET),
+        ldtk_handle: levels_project.0.clone().into(),
+        transform: Transform::from_translation(level_bounds.offset()),
         ..Default::default()
     });
 }
 
+// ldtk关卡生成时按关卡尺寸更新LevelBounds，并将关卡移到原点居中
+pub fn update_ldtk_level_bounds(
+    mut level_events: EventReader<LevelEvent>,
+    levels_project: Res<LevelsProject>,
+    ldtk_projects: Res<Assets<LdtkProject>>,
+    mut level_bounds: ResMut<LevelBounds>,
+    mut q_ldtk_world: Query<&mut Transform, With<LdtkProjectHandle>>,
+) {
+    for event in level_events.read() {
+        let LevelEvent::Spawned(level_iid) = event else {
+            continue;
+        };
+        let Some(level) = ldtk_projects
+            .get(&levels_project.0)
+            .and_then(|project| project.get_raw_level_by_iid(level_iid.get()))
+        else {
+            continue;
+        };
+        let bounds = LevelBounds::from_px(level.px_wid, level.px_hei);
+        if *level_bounds != bounds {
+            info!("Level size {}x{}", bounds.columns, bounds.rows);
+            *level_bounds = bounds;
+        }
+        for mut transform in &mut q_ldtk_world {
+            transform.translation = bounds.offset();
+        }
+    }
+}
+
 pub fn spawn_ldtk_entity(
     mut commands: Commands,
     entity_query: Query<(Entity, &Transform, &EntityInstance), Added<EntityInstance>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
-    asset_server: Res<AssetServer>,
+    sprite_assets: Res<SpriteAssets>,
+    level_bounds: Res<LevelBounds>,
 ) {
     for (_entity, transform, entity_instance) in entity_query.iter() {
         if entity_instance.identifier == *"Tree" {
-            let map_texture_handle = asset_server.load("textures/map.bmp");
-            let map_texture_atlas =
-                TextureAtlasLayout::from_grid(UVec2::new(32, 32), 7, 1, None, None);
-            let map_texture_atlas_handle = texture_atlases.add(map_texture_atlas);
-
-            let mut translation = transform.translation + LEVEL_TRANSLATION_OFFSET;
+            let mut translation = transform.translation + level_bounds.offset();
             translation.z = SPRITE_TREE_ORDER;
             commands.spawn((
                 LevelItem::Tree,
                 Sprite {
-                    image: map_texture_handle,
+                    image: sprite_assets.map.clone(),
                     texture_atlas: Some(TextureAtlas {
                         index: 2,
-                        layout: map_texture_atlas_handle,
+                        layout: sprite_assets.map_layout.clone(),
                     }),
                     ..default()
                 },
@@ -228,22 +336,83 @@ pub fn animate_water(
     }
 }
 
+// 已生成的敌人数量达到最大值 并且 敌人全部阵亡，本关通关
+pub fn check_stage_clear(
+    q_enemies: Query<(), With<Enemy>>,
+    level_spawned_enemies: Res<LevelSpawnedEnemies>,
+    mut stage_clear_timer: ResMut<StageClearTimer>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if level_spawned_enemies.0 == ENEMIES_PER_LEVEL && q_enemies.iter().len() == 0 {
+        info!("Stage clear");
+        stage_clear_timer.0.reset();
+        app_state.set(AppState::StageClear);
+    }
+}
+
+// 胜利音乐播放完后切换到下一关卡
 pub fn auto_switch_level(
     mut commands: Commands,
-    q_enemies: Query<(), With<Enemy>>,
+    time: Res<Time>,
+    mut stage_clear_timer: ResMut<StageClearTimer>,
     q_players: Query<Entity, With<PlayerNo>>,
     q_level_items: Query<Entity, With<LevelItem>>,
     mut level_selection: ResMut<LevelSelection>,
     mut level_spawned_enemies: ResMut<LevelSpawnedEnemies>,
     mut app_state: ResMut<NextState<AppState>>,
+    mut custom_level: ResMut<CustomLevel>,
+    mut generated_level: ResMut<GeneratedLevel>,
+    mut level_pack: ResMut<LevelPack>,
+    q_custom_level: Query<Entity, With<CustomLevelRoot>>,
+    editor_test_play: Res<EditorTestPlay>,
+    sprite_assets: Res<SpriteAssets>,
 ) {
-    // 已生成的敌人数量达到最大值 并且 敌人全部阵亡，切换到下一关卡
-    if level_spawned_enemies.0 == ENEMIES_PER_LEVEL && q_enemies.iter().len() == 0 {
+    if stage_clear_timer.0.tick(time.delta()).just_finished() {
+        let next_map = match (&custom_level.0, generated_level.0) {
+            (Some(map), Some(seed)) => {
+                // 随机关卡通关后生成下一关
+                let seed = seed.wrapping_add(1);
+                info!("Generate next random level, seed={}", seed);
+                generated_level.0 = Some(seed);
+                generate_level(seed, map.width, map.height)
+                    .map_err(|e| error!("Failed to generate next level: {}", e))
+                    .ok()
+            }
+            (Some(_), None) if level_pack.current + 1 < level_pack.maps.len() => {
+                // 关卡包中的下一关
+                level_pack.current += 1;
+                info!("Switch to next level in pack, index={}", level_pack.current);
+                Some(level_pack.maps[level_pack.current].clone())
+            }
+            _ => None,
+        };
+        if let Some(map) = next_map {
+            level_spawned_enemies.0 = 0;
+            for player in &q_players {
+                commands.entity(player).despawn_recursive();
+            }
+            for custom_level_root in &q_custom_level {
+                commands.entity(custom_level_root).despawn_recursive();
+            }
+            spawn_level_map(&map, &mut commands, &sprite_assets);
+            custom_level.0 = Some(map);
+            app_state.set(AppState::Playing);
+            return;
+        }
+        if custom_level.0.is_some() {
+            // 自定义关卡全部通关
+            info!("Custom level cleared");
+            app_state.set(if editor_test_play.0 {
+                AppState::Editor
+            } else {
+                AppState::Victory
+            });
+            return;
+        }
         if let LevelSelection::Indices(LevelIndices { level, .. }) = *level_selection {
             if level as i32 == MAX_LEVELS - 1 {
-                // TODO 游戏胜利
                 info!("win the game!");
-                app_state.set(AppState::StartMenu);
+                app_state.set(AppState::Victory);
             } else {
                 // 下一关卡
                 info!("Switch to next level, index={}", level + 1);
@@ -257,6 +426,7 @@ pub fn auto_switch_level(
                 for level_item in &q_level_items {
                     commands.entity(level_item).despawn_recursive();
                 }
+                app_state.set(AppState::Playing);
             }
         }
     }
@@ -292,6 +462,24 @@ pub fn cleanup_ldtk_world(
     }
 }
 
-pub fn reset_level_selection(mut level_selection: ResMut<LevelSelection>) {
-    *level_selection = LevelSelection::index(0);
+// 从1开始的关卡序号，用于显示和排行榜
+pub fn stage_number(
+    level_selection: &LevelSelection,
+    custom_level: &CustomLevel,
+    level_pack: &LevelPack,
+) -> usize {
+    if custom_level.0.is_some() {
+        level_pack.current + 1
+    } else if let LevelSelection::Indices(LevelIndices { level, .. }) = *level_selection {
+        level + 1
+    } else {
+        1
+    }
+}
+
+pub fn reset_level_selection(
+    mut level_selection: ResMut<LevelSelection>,
+    launch_options: Res<LaunchOptions>,
+) {
+    *level_selection = LevelSelection::index(launch_options.start_level());
 }
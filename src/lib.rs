@@ -0,0 +1,29 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod area;
+pub mod audio;
+pub mod bullet;
+pub mod camera;
+pub mod cli;
+pub mod common;
+pub mod debug;
+pub mod editor;
+pub mod enemy;
+pub mod event_log;
+pub mod generator;
+pub mod high_score;
+pub mod level;
+pub mod lint;
+pub mod locale;
+pub mod map;
+pub mod minimap;
+pub mod net;
+pub mod player;
+pub mod save_game;
+pub mod settings;
+pub mod sprites;
+pub mod stage_select;
+pub mod storage;
+pub mod survival;
+pub mod tile_grid;
+pub mod ui;
@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::{raw_level_accessor::RawLevelAccessor, LdtkJson, Level};
+use std::fmt;
+use std::path::Path;
+
+use crate::common::TILE_SIZE;
+use crate::map::{LevelMap, MapTile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintIssue {
+    pub level: String,
+    pub severity: LintSeverity,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", self.level, severity, self.message)
+    }
+}
+
+// 待检查的关卡，保留原始实体列表以便发现重复和重叠
+#[derive(Debug, Clone)]
+pub struct LintLevel {
+    pub name: String,
+    pub px_width: i32,
+    pub px_height: i32,
+    pub placements: Vec<(MapTile, IVec2)>,
+    // 无法识别的ldtk实体名称和位置
+    pub unknown_entities: Vec<(String, IVec2)>,
+}
+
+impl LintLevel {
+    pub fn from_map(name: &str, map: &LevelMap) -> Self {
+        let placements = MapTile::ALL
+            .iter()
+            .filter(|tile| **tile != MapTile::Empty)
+            .flat_map(|tile| map.positions(*tile).into_iter().map(|pos| (*tile, pos)))
+            .collect();
+        LintLevel {
+            name: name.to_string(),
+            px_width: map.width * TILE_SIZE as i32,
+            px_height: map.height * TILE_SIZE as i32,
+            placements,
+            unknown_entities: Vec::new(),
+        }
+    }
+
+    // 转换成自定义关卡，超出范围的格子和未知实体被忽略
+    pub fn to_map(&self) -> LevelMap {
+        let tiles = |px: i32| ((px + TILE_SIZE as i32 - 1) / TILE_SIZE as i32).max(1);
+        let mut map = LevelMap::new(tiles(self.px_width), tiles(self.px_height));
+        for (tile, pos) in &self.placements {
+            map.set(pos.x, pos.y, *tile);
+        }
+        map
+    }
+}
+
+// 读取关卡文件，支持ldtk文件和自定义关卡文本文件
+pub fn load_lint_levels(path: &str) -> Result<Vec<LintLevel>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    if path.ends_with(".ldtk") {
+        levels_from_ldtk(&text, Path::new(path).parent().unwrap_or(Path::new("")))
+    } else {
+        let map = LevelMap::from_text(&text)?;
+        let name = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(vec![LintLevel::from_map(&name, &map)])
+    }
+}
+
+// 读取ldtk文件中的所有关卡，外部关卡文件相对于ldtk文件所在目录
+pub fn levels_from_ldtk(text: &str, base_dir: &Path) -> Result<Vec<LintLevel>, String> {
+    let ldtk: LdtkJson = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let mut levels = Vec::new();
+    for level in ldtk.iter_raw_levels() {
+        let external_level;
+        let level = match (&level.layer_instances, &level.external_rel_path) {
+            (None, Some(rel_path)) => {
+                let path = base_dir.join(rel_path);
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                external_level = serde_json::from_str::<Level>(&text)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                &external_level
+            }
+            _ => level,
+        };
+        let mut placements = Vec::new();
+        let mut unknown_entities = Vec::new();
+        for layer in level.layer_instances.iter().flatten() {
+            for entity in &layer.entity_instances {
+                match MapTile::from_name(&entity.identifier) {
+                    Some(tile) => placements.push((tile, entity.grid)),
+                    None => unknown_entities.push((entity.identifier.clone(), entity.grid)),
+                }
+            }
+        }
+        levels.push(LintLevel {
+            name: level.identifier.clone(),
+            px_width: level.px_wid,
+            px_height: level.px_hei,
+            placements,
+            unknown_entities,
+        });
+    }
+    Ok(levels)
+}
+
+pub fn lint_level(level: &LintLevel) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut report = |severity: LintSeverity, message: String| {
+        issues.push(LintIssue {
+            level: level.name.clone(),
+            severity,
+            message,
+        });
+    };
+
+    // 关卡可以是任意尺寸，但需要是整数个格子
+    let tile_px = TILE_SIZE as i32;
+    if level.px_width % tile_px != 0 || level.px_height % tile_px != 0 {
+        report(
+            LintSeverity::Warning,
+            format!(
+                "level size {}x{} px is not a multiple of the {} px tile",
+                level.px_width, level.px_height, tile_px
+            ),
+        );
+    }
+
+    for (name, pos) in &level.unknown_entities {
+        report(
+            LintSeverity::Warning,
+            format!(
+                "unknown entity {} at ({}, {}) is ignored",
+                name, pos.x, pos.y
+            ),
+        );
+    }
+
+    // 超出地图范围的实体
+    let tiles = |px: i32| ((px + tile_px - 1) / tile_px).max(1);
+    let mut map = LevelMap::new(tiles(level.px_width), tiles(level.px_height));
+    let mut markers = Vec::new();
+    for (tile, pos) in &level.placements {
+        if !map.in_bounds(pos.x, pos.y) {
+            report(
+                LintSeverity::Error,
+                format!(
+                    "{} at ({}, {}) is outside the {}x{} grid",
+                    tile.name(),
+                    pos.x,
+                    pos.y,
+                    map.width,
+                    map.height
+                ),
+            );
+            continue;
+        }
+        match tile {
+            MapTile::Player1 | MapTile::Player2 | MapTile::Enemies => markers.push((*tile, *pos)),
+            _ => {
+                if map.get(pos.x, pos.y) != MapTile::Empty {
+                    report(
+                        LintSeverity::Warning,
+                        format!(
+                            "{} at ({}, {}) overlaps {}",
+                            tile.name(),
+                            pos.x,
+                            pos.y,
+                            map.get(pos.x, pos.y).name()
+                        ),
+                    );
+                }
+                map.tiles[(pos.y * map.width + pos.x) as usize] = *tile;
+            }
+        }
+    }
+
+    // 出生点不能和实心格子重叠
+    for (tile, pos) in &markers {
+        let under = map.get(pos.x, pos.y);
+        if under.is_solid() {
+            report(
+                LintSeverity::Error,
+                format!(
+                    "{} marker at ({}, {}) overlaps solid {}",
+                    tile.name(),
+                    pos.x,
+                    pos.y,
+                    under.name()
+                ),
+            );
+        }
+    }
+
+    // 家和玩家出生点的数量
+    let (width, height) = (map.width, map.height);
+    let in_grid = |pos: &IVec2| (0..width).contains(&pos.x) && (0..height).contains(&pos.y);
+    let count = |tile: MapTile| {
+        level
+            .placements
+            .iter()
+            .filter(|(t, pos)| *t == tile && in_grid(pos))
+            .count()
+    };
+    for (tile, required) in [
+        (MapTile::Home, LintSeverity::Error),
+        (MapTile::Player1, LintSeverity::Error),
+        (MapTile::Player2, LintSeverity::Warning),
+    ] {
+        match count(tile) {
+            0 => report(required, format!("missing {}", tile.name())),
+            1 => {}
+            n => report(
+                LintSeverity::Error,
+                format!("{} duplicate {}", n, tile.name()),
+            ),
+        }
+    }
+    if count(MapTile::Enemies) == 0 {
+        report(
+            LintSeverity::Error,
+            "missing Enemies marker, no enemy will ever spawn".to_string(),
+        );
+    }
+
+    // 出生点到家的可达性
+    for (tile, pos) in &markers {
+        map.tiles[(pos.y * map.width + pos.x) as usize] = *tile;
+    }
+    if count(MapTile::Home) > 0 {
+        for pos in map.unreachable_spawns() {
+            report(
+                LintSeverity::Error,
+                format!(
+                    "{} at ({}, {}) cannot reach the Home",
+                    map.get(pos.x, pos.y).name(),
+                    pos.x,
+                    pos.y
+                ),
+            );
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,406 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+pub const DEFAULT_FONT: &str = "fonts/FiraMono-Medium.ttf";
+// 中文字体不随仓库分发，需自行放到 assets/fonts 下，缺失时设置中隐藏中文并使用英文界面
+const CHINESE_FONT: &str = "fonts/NotoSansSC-Regular.ttf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Chinese,
+}
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "ENGLISH",
+            Language::Chinese => "中文",
+        }
+    }
+
+    pub fn font(self) -> &'static str {
+        match self {
+            Language::English => DEFAULT_FONT,
+            Language::Chinese => CHINESE_FONT,
+        }
+    }
+
+    pub fn next(self) -> Language {
+        match self {
+            Language::English => Language::Chinese,
+            Language::Chinese => Language::English,
+        }
+    }
+}
+
+// 界面文字的字符串表
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKey {
+    Title,
+    OnePlayer,
+    TwoPlayers,
+    StageSelect,
+    Construction,
+    RandomMap,
+    HighScores,
+    Options,
+    Continue,
+    GameOver,
+    Victory,
+    ContinueThisStage,
+    StartMenu,
+    Paused,
+    Resume,
+    RestartStage,
+    QuitToMenu,
+    NewHighScore,
+    EnterInitials,
+    InitialsHelp,
+    Rank,
+    Name,
+    Score,
+    Stage,
+    Mode,
+    Difficulty,
+    NoRecords,
+    PressEnterToReturn,
+    SelectStage,
+    Locked,
+    StageSelectHelp,
+    EditorHelp,
+    EditorNewLevel,
+    EditorLoaded,
+    EditorLoadFailed,
+    EditorSaved,
+    EditorSaveFailed,
+    EditorGenerated,
+    EditorGenerateFailed,
+    EditorTestPlayOnePlayer,
+    EditorTestPlayTwoPlayers,
+    EditorNeedsHome,
+    EditorNeedsPlayer1,
+    EditorNeedsEnemies,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    UiVolume,
+    Display,
+    Fullscreen,
+    Windowed,
+    IntegerScaling,
+    Vsync,
+    StartingLives,
+    Language,
+    Back,
+    On,
+    Off,
+    Lives,
+    Enemies,
+    LanGame,
+    HostGame,
+    JoinGame,
+    CoOp,
+    Versus,
+    WaitingForPlayer,
+    Connecting,
+    ConnectionTimedOut,
+    ConnectionLost,
+    HostLeft,
+    Player2Left,
+    NetError,
+    StageClear,
+    LobbyHelp,
+    Watch,
+    Spectating,
+    AllView,
+    SwitchViewHelp,
+    Survival,
+    Wave,
+    Time,
+    SurvivalHighScores,
+    SwitchBoardHelp,
+}
+
+impl TextKey {
+    pub fn get(self, language: Language) -> &'static str {
+        match language {
+            Language::English => self.english(),
+            Language::Chinese => self.chinese(),
+        }
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            TextKey::Title => "BATTLE\nCITY",
+            TextKey::OnePlayer => "1 PLAYER",
+            TextKey::TwoPlayers => "2 PLAYERS",
+            TextKey::StageSelect => "STAGE SELECT",
+            TextKey::Construction => "CONSTRUCTION",
+            TextKey::RandomMap => "RANDOM MAP",
+            TextKey::HighScores => "HIGH SCORES",
+            TextKey::Options => "OPTIONS",
+            TextKey::Continue => "CONTINUE",
+            TextKey::GameOver => "GAME\nOVER",
+            TextKey::Victory => "VICTORY",
+            TextKey::ContinueThisStage => "CONTINUE THIS STAGE",
+            TextKey::StartMenu => "START MENU",
+            TextKey::Paused => "PAUSED",
+            TextKey::Resume => "RESUME",
+            TextKey::RestartStage => "RESTART STAGE",
+            TextKey::QuitToMenu => "QUIT TO MENU",
+            TextKey::NewHighScore => "NEW HIGH SCORE",
+            TextKey::EnterInitials => "ENTER YOUR INITIALS",
+            TextKey::InitialsHelp => "UP/DOWN LETTER  LEFT/RIGHT MOVE  ENTER OK",
+            TextKey::Rank => "RANK",
+            TextKey::Name => "NAME",
+            TextKey::Score => "SCORE",
+            TextKey::Stage => "STAGE",
+            TextKey::Mode => "MODE",
+            TextKey::Difficulty => "DIFFICULTY",
+            TextKey::NoRecords => "NO RECORDS",
+            TextKey::PressEnterToReturn => "PRESS ENTER TO RETURN",
+            TextKey::SelectStage => "SELECT STAGE",
+            TextKey::Locked => "LOCKED",
+            TextKey::StageSelectHelp => "LEFT/RIGHT select   ENTER start   ESC back",
+            TextKey::EditorHelp => {
+                "Arrows/Mouse  move\nSpace/LMB     paint\nDel/RMB       erase\n\
+                 Tab           next tile\nM             1P/2P\nT             test play\n\
+                 S / L         save / load\nN / G         new / random\nEsc           menu"
+            }
+            TextKey::EditorNewLevel => "New level",
+            TextKey::EditorLoaded => "Loaded",
+            TextKey::EditorLoadFailed => "Load failed:",
+            TextKey::EditorSaved => "Saved",
+            TextKey::EditorSaveFailed => "Save failed:",
+            TextKey::EditorGenerated => "Generated seed",
+            TextKey::EditorGenerateFailed => "Generate failed:",
+            TextKey::EditorTestPlayOnePlayer => "Test play: 1 player",
+            TextKey::EditorTestPlayTwoPlayers => "Test play: 2 players",
+            TextKey::EditorNeedsHome => "Test play needs a Home",
+            TextKey::EditorNeedsPlayer1 => "Test play needs a Player1",
+            TextKey::EditorNeedsEnemies => "Test play needs an Enemies marker",
+            TextKey::MasterVolume => "MASTER VOLUME",
+            TextKey::MusicVolume => "MUSIC VOLUME",
+            TextKey::SfxVolume => "SFX VOLUME",
+            TextKey::UiVolume => "UI VOLUME",
+            TextKey::Display => "DISPLAY",
+            TextKey::Fullscreen => "FULLSCREEN",
+            TextKey::Windowed => "WINDOWED",
+            TextKey::IntegerScaling => "INTEGER SCALING",
+            TextKey::Vsync => "VSYNC",
+            TextKey::StartingLives => "STARTING LIVES",
+            TextKey::Language => "LANGUAGE",
+            TextKey::Back => "BACK",
+            TextKey::On => "ON",
+            TextKey::Off => "OFF",
+            TextKey::Lives => "LIVES",
+            TextKey::Enemies => "ENEMIES",
+            TextKey::LanGame => "LAN GAME",
+            TextKey::HostGame => "HOST GAME",
+            TextKey::JoinGame => "JOIN",
+            TextKey::CoOp => "CO-OP",
+            TextKey::Versus => "VERSUS",
+            TextKey::WaitingForPlayer => "WAITING FOR PLAYER 2 ON PORT",
+            TextKey::Connecting => "CONNECTING TO",
+            TextKey::ConnectionTimedOut => "CONNECTION TIMED OUT",
+            TextKey::ConnectionLost => "CONNECTION LOST",
+            TextKey::HostLeft => "HOST LEFT THE GAME",
+            TextKey::Player2Left => "PLAYER 2 DISCONNECTED",
+            TextKey::NetError => "NETWORK ERROR",
+            TextKey::StageClear => "STAGE CLEAR",
+            TextKey::LobbyHelp => "TYPE ADDRESS ON JOIN/WATCH   ENTER select   ESC cancel/back",
+            TextKey::Watch => "WATCH",
+            TextKey::Spectating => "SPECTATING",
+            TextKey::AllView => "ALL",
+            TextKey::SwitchViewHelp => "TAB switch view   ESC leave",
+            TextKey::Survival => "SURVIVAL",
+            TextKey::Wave => "WAVE",
+            TextKey::Time => "TIME",
+            TextKey::SurvivalHighScores => "SURVIVAL HIGH SCORES",
+            TextKey::SwitchBoardHelp => "LEFT/RIGHT SWITCH BOARD",
+        }
+    }
+
+    fn chinese(self) -> &'static str {
+        match self {
+            TextKey::Title => "坦克大战",
+            TextKey::OnePlayer => "单人游戏",
+            TextKey::TwoPlayers => "双人游戏",
+            TextKey::StageSelect => "选择关卡",
+            TextKey::Construction => "关卡编辑",
+            TextKey::RandomMap => "随机地图",
+            TextKey::HighScores => "排行榜",
+            TextKey::Options => "设置",
+            TextKey::Continue => "继续游戏",
+            TextKey::GameOver => "游戏\n结束",
+            TextKey::Victory => "恭喜通关",
+            TextKey::ContinueThisStage => "从本关继续",
+            TextKey::StartMenu => "返回主菜单",
+            TextKey::Paused => "暂停",
+            TextKey::Resume => "继续",
+            TextKey::RestartStage => "重新开始本关",
+            TextKey::QuitToMenu => "退出到主菜单",
+            TextKey::NewHighScore => "新纪录",
+            TextKey::EnterInitials => "请输入名字缩写",
+            TextKey::InitialsHelp => "上下 选择字母  左右 移动  回车 确认",
+            TextKey::Rank => "名次",
+            TextKey::Name => "名字",
+            TextKey::Score => "得分",
+            TextKey::Stage => "关卡",
+            TextKey::Mode => "模式",
+            TextKey::Difficulty => "难度",
+            TextKey::NoRecords => "暂无记录",
+            TextKey::PressEnterToReturn => "按回车返回",
+            TextKey::SelectStage => "选择关卡",
+            TextKey::Locked => "未解锁",
+            TextKey::StageSelectHelp => "左右 选择   回车 开始   ESC 返回",
+            TextKey::EditorHelp => {
+                "方向键/鼠标  移动\n空格/左键    放置\nDel/右键     擦除\n\
+                 Tab          下一种地形\nM            单人/双人\nT            试玩\n\
+                 S / L        保存 / 读取\nN / G        新建 / 随机\nEsc          菜单"
+            }
+            TextKey::EditorNewLevel => "新关卡",
+            TextKey::EditorLoaded => "已读取",
+            TextKey::EditorLoadFailed => "读取失败：",
+            TextKey::EditorSaved => "已保存",
+            TextKey::EditorSaveFailed => "保存失败：",
+            TextKey::EditorGenerated => "已随机生成，种子",
+            TextKey::EditorGenerateFailed => "随机生成失败：",
+            TextKey::EditorTestPlayOnePlayer => "试玩：单人",
+            TextKey::EditorTestPlayTwoPlayers => "试玩：双人",
+            TextKey::EditorNeedsHome => "试玩需要放置家",
+            TextKey::EditorNeedsPlayer1 => "试玩需要放置玩家1出生点",
+            TextKey::EditorNeedsEnemies => "试玩需要放置敌人出生点",
+            TextKey::MasterVolume => "总音量",
+            TextKey::MusicVolume => "音乐音量",
+            TextKey::SfxVolume => "音效音量",
+            TextKey::UiVolume => "界面音量",
+            TextKey::Display => "显示",
+            TextKey::Fullscreen => "全屏",
+            TextKey::Windowed => "窗口",
+            TextKey::IntegerScaling => "整数倍缩放",
+            TextKey::Vsync => "垂直同步",
+            TextKey::StartingLives => "初始生命",
+            TextKey::Language => "语言",
+            TextKey::Back => "返回",
+            TextKey::On => "开",
+            TextKey::Off => "关",
+            TextKey::Lives => "生命",
+            TextKey::Enemies => "敌人",
+            TextKey::LanGame => "局域网联机",
+            TextKey::HostGame => "创建主机",
+            TextKey::JoinGame => "加入",
+            TextKey::CoOp => "合作",
+            TextKey::Versus => "对战",
+            TextKey::WaitingForPlayer => "等待玩家2加入，端口",
+            TextKey::Connecting => "正在连接",
+            TextKey::ConnectionTimedOut => "连接超时",
+            TextKey::ConnectionLost => "连接已断开",
+            TextKey::HostLeft => "主机已退出",
+            TextKey::Player2Left => "玩家2已断开",
+            TextKey::NetError => "网络错误",
+            TextKey::StageClear => "本关通过",
+            TextKey::LobbyHelp => "在加入/观战一栏输入地址   回车 选择   ESC 取消/返回",
+            TextKey::Watch => "观战",
+            TextKey::Spectating => "观战中",
+            TextKey::AllView => "全景",
+            TextKey::SwitchViewHelp => "Tab 切换视角   ESC 离开",
+            TextKey::Survival => "生存模式",
+            TextKey::Wave => "波次",
+            TextKey::Time => "时间",
+            TextKey::SurvivalHighScores => "生存模式排行榜",
+            TextKey::SwitchBoardHelp => "左右 切换排行榜",
+        }
+    }
+}
+
+// 当前语言和对应的界面字体，设置中切换语言时更新
+#[derive(Debug, Resource)]
+pub struct Locale {
+    pub language: Language,
+    pub font: Handle<Font>,
+    // 启动时就加载中文字体，加载失败后不能再切换到中文
+    chinese_font: Handle<Font>,
+    chinese_unavailable: bool,
+}
+
+impl Locale {
+    fn new(language: Language, asset_server: &AssetServer) -> Self {
+        Locale {
+            language,
+            font: asset_server.load(language.font()),
+            chinese_font: asset_server.load(CHINESE_FONT),
+            chinese_unavailable: false,
+        }
+    }
+
+    pub fn is_available(&self, language: Language) -> bool {
+        language != Language::Chinese || !self.chinese_unavailable
+    }
+
+    pub fn tr(&self, key: TextKey) -> &'static str {
+        key.get(self.language)
+    }
+
+    pub fn text_font(&self, font_size: f32) -> TextFont {
+        TextFont {
+            font: self.font.clone(),
+            font_size,
+            ..default()
+        }
+    }
+}
+
+// 初始状态的界面在Startup之前创建，需要在构建App时插入
+impl FromWorld for Locale {
+    fn from_world(world: &mut World) -> Self {
+        let language = world.resource::<Settings>().language;
+        Locale::new(language, world.resource::<AssetServer>())
+    }
+}
+
+pub fn update_locale(
+    mut settings: ResMut<Settings>,
+    asset_server: Res<AssetServer>,
+    mut locale: ResMut<Locale>,
+) {
+    // 默认字体没有中文字形，缺少中文字体时退回英文，避免显示成方框
+    if !locale.chinese_unavailable && asset_server.load_state(&locale.chinese_font).is_failed() {
+        warn!("Font {} not found, Chinese is unavailable", CHINESE_FONT);
+        locale.chinese_unavailable = true;
+        if settings.language == Language::Chinese {
+            settings.language = Language::English;
+        }
+    }
+    if settings.language != locale.language {
+        locale.language = settings.language;
+        locale.font = asset_server.load(settings.language.font());
+    }
+}
+
+// 语言切换时仍显示在屏幕上的静态文字
+#[derive(Component)]
+pub struct LocalizedText(pub TextKey);
+
+pub fn update_localized_text(
+    locale: Res<Locale>,
+    mut q_texts: Query<(&LocalizedText, &mut Text)>,
+    mut q_fonts: Query<&mut TextFont>,
+) {
+    if !locale.is_changed() {
+        return;
+    }
+    for (localized_text, mut text) in &mut q_texts {
+        text.0 = locale.tr(localized_text.0).to_string();
+    }
+    // 所有界面文字都使用当前语言的字体
+    for mut text_font in &mut q_fonts {
+        if text_font.font != locale.font {
+            text_font.font = locale.font.clone();
+        }
+    }
+}
@@ -1,20 +1,39 @@
-mod area;
-mod bullet;
-mod common;
-mod enemy;
-mod level;
-mod player;
-mod ui;
-
-use area::*;
-use bullet::*;
-use common::*;
-use enemy::*;
-use level::*;
-use player::*;
-use ui::*;
+use battle_city::{
+    area::*,
+    audio::*,
+    bullet::*,
+    camera::*,
+    cli::*,
+    common::*,
+    debug::*,
+    editor::*,
+    enemy::*,
+    event_log::*,
+    generator::*,
+    high_score::*,
+    level::{self, *},
+    locale::*,
+    map::*,
+    minimap::*,
+    net::*,
+    player::*,
+    save_game::*,
+    settings::*,
+    sprites::*,
+    stage_select::*,
+    survival::*,
+    tile_grid::*,
+    ui::*,
+};
 
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::diagnostic::{EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy::render::view::VisibilitySystems;
+use bevy::render::RenderPlugin;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
@@ -22,24 +41,161 @@ const BACKGROUND_COLOR: Color = Color::BLACK;
 
 // TODO 坦克碰撞导致被迫移动
 fn main() {
-    App::new()
-        .register_type::<PlayerNo>()
-        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-        // .add_plugin(RapierDebugRenderPlugin::default())
-        .add_plugins(LdtkPlugin)
+    let launch_options = match LaunchOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if launch_options.help {
+        println!("{}", USAGE);
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(e) = launch_options.validate_ldtk_start_level() {
+        eprintln!("failed to load levels: {}", e);
+        std::process::exit(1);
+    }
+    let level_pack = match launch_options.load_level_pack() {
+        Ok(maps) => LevelPack { maps, current: 0 },
+        Err(e) => {
+            eprintln!("failed to load levels: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let game_event_log = match &launch_options.event_log {
+        Some(path) => match GameEventLog::create(path) {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("failed to create event log: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => GameEventLog::default(),
+    };
+
+    // 清理关卡、坦克等实体并重置生命和得分，保留当前关卡选择
+    let cleanup_stage = (
+        cleanup_level_items,
+        cleanup_ldtk_world,
+        cleanup_custom_level,
+        cleanup_players,
+        cleanup_born,
+        cleanup_bullets,
+        cleanup_explosions,
+        cleanup_enemies,
+        reset_player_lives,
+        reset_player_scores,
+        reset_cheats_used,
+        reset_level_spawned_enemies,
+        reset_survival_waves,
+        despawn_screen::<OnHudScreen>,
+    );
+    // 离开对局时清理并回到第一关
+    let cleanup_game = (cleanup_stage, reset_level_selection);
+
+    let settings = Settings::load();
+
+    let mut app = App::new();
+    if launch_options.headless {
+        // 无窗口、无渲染运行，用于自动化测试
+        app.add_plugins((
+            DefaultPlugins
+                .set(ImagePlugin::default_nearest())
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+            ScheduleRunnerPlugin::run_loop(std::time::Duration::from_secs_f64(1.0 / 60.0)),
+        ))
+        .init_asset::<LdtkProject>();
+    } else {
+        // ldtk关卡依赖tilemap渲染，无渲染时转换成自定义关卡
+        app.add_plugins((
+            DefaultPlugins.set(ImagePlugin::default_nearest()),
+            LdtkPlugin,
+            // F3切换显示碰撞体轮廓
+            RapierDebugRenderPlugin {
+                enabled: false,
+                ..default()
+            },
+        ));
+    }
+    app.register_type::<PlayerNo>()
+        .add_plugins((
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0),
+            FrameTimeDiagnosticsPlugin,
+            EntityCountDiagnosticsPlugin,
+        ))
         .add_event::<ExplosionEvent>()
         .add_event::<SpawnPlayerEvent>()
         .add_event::<HomeDyingEvent>()
+        .add_event::<SoundEvent>()
+        .add_event::<GameEvent>()
+        .add_event::<ConsoleCommand>()
         .init_state::<AppState>()
         .insert_resource(ClearColor(BACKGROUND_COLOR))
+        .insert_resource(launch_options)
+        .insert_resource(level_pack)
+        .insert_resource(game_event_log)
+        .init_resource::<GameTick>()
+        // 初始状态的OnEnter早于Startup执行，开始菜单依赖的存档需要提前读取
+        .insert_resource(HighScores::load(HIGH_SCORE_FILE))
+        .insert_resource(SurvivalHighScores(HighScores::load(
+            SURVIVAL_HIGH_SCORE_FILE,
+        )))
+        .insert_resource(SaveSlot::load())
+        .insert_resource(PlayerLives {
+            player1: settings.starting_lives,
+            player2: settings.starting_lives,
+        })
+        .insert_resource(settings)
+        .init_resource::<Locale>()
+        .init_resource::<SpriteAssets>()
+        .init_resource::<TileGrid>()
+        .init_resource::<LevelBounds>()
+        .init_resource::<SplitScreen>()
+        .init_resource::<OptionsMenu>()
+        .init_resource::<AudioMixer>()
+        .init_resource::<MusicPlayer>()
+        .init_resource::<StageClearTimer>()
         .insert_resource(MultiplayerMode::SinglePlayer)
+        .init_resource::<StartMenuCursor>()
+        .init_resource::<CustomLevel>()
+        .init_resource::<GeneratedLevel>()
+        .init_resource::<EditorTestPlay>()
+        .insert_resource(UnlockedStages::load())
+        .init_resource::<StageSelectCursor>()
+        .init_resource::<GameOverCursor>()
+        .init_resource::<PauseMenuCursor>()
+        .init_resource::<PlayerScores>()
+        .init_resource::<PlayerInputs>()
+        .init_resource::<VersusMode>()
+        .init_resource::<GodMode>()
+        .init_resource::<CheatsUsed>()
+        .init_resource::<DebugOverlay>()
+        .init_resource::<DevConsole>()
+        .init_resource::<Lobby>()
+        .init_resource::<NetClientView>()
+        .init_resource::<Difficulty>()
+        .init_resource::<PendingHighScore>()
+        .init_resource::<InitialsEntry>()
+        .init_resource::<HighScoreBoard>()
+        .init_resource::<SurvivalMode>()
+        .init_resource::<SurvivalWaves>()
         .insert_resource(LevelSelection::index(0))
         .insert_resource(LevelSpawnedEnemies(0))
-        .insert_resource(PlayerLives {
-            player1: 3,
-            player2: 3,
-        })
         .register_ldtk_entity::<level::StoneWallBundle>("StoneWall")
         .register_ldtk_entity::<level::IronWallBundle>("IronWall")
         .register_ldtk_entity::<level::WaterBundle>("Water")
@@ -52,37 +208,153 @@ fn main() {
             (
                 setup_camera,
                 setup_rapier,
-                setup_wall,
+                setup_levels_project,
                 setup_explosion_assets,
                 setup_game_sounds,
+                skip_start_menu,
             ),
         )
+        .add_systems(
+            Update,
+            (
+                exit_after_frames,
+                play_sounds,
+                play_music,
+                toggle_mute,
+                update_locale,
+                update_localized_text,
+                duck_while_paused,
+                update_mixer_volume,
+                apply_window_settings,
+                apply_camera_scaling,
+                toggle_debug_overlay.run_if(dev_tools_enabled),
+                build_explosion_atlases,
+                rebuild_wall,
+            ),
+        )
+        .add_systems(Update, update_tile_grid.before(enemies_move))
+        .add_systems(
+            Update,
+            update_ldtk_level_bounds
+                .before(spawn_ldtk_entity)
+                .before(auto_spawn_players),
+        )
+        .add_systems(
+            Update,
+            (
+                update_split_screen,
+                apply_split_screen,
+                follow_players_camera.run_if(in_state(AppState::Playing)),
+            )
+                .chain()
+                .after(players_move)
+                .before(apply_camera_scaling),
+        )
+        .add_systems(
+            Update,
+            (update_debug_stats, draw_enemy_debug, update_debug_labels)
+                .after(toggle_debug_overlay)
+                .run_if(debug_overlay_enabled),
+        )
+        .add_systems(OnEnter(AppState::Options), (setup_options_menu,))
+        .add_systems(
+            Update,
+            (options_menu_input, update_options_screen)
+                .chain()
+                .run_if(in_state(AppState::Options)),
+        )
+        .add_systems(
+            OnExit(AppState::Options),
+            (despawn_screen::<OnOptionsScreen>, save_settings),
+        )
         .add_systems(
             OnEnter(AppState::StartMenu),
             (
                 setup_start_menu,
-                cleanup_level_items,
-                cleanup_ldtk_world,
-                cleanup_players,
-                cleanup_born,
-                cleanup_bullets,
-                cleanup_explosions,
-                cleanup_enemies,
-                reset_player_lives,
-                reset_level_selection,
-                reset_level_spawned_enemies,
+                reset_camera,
+                log_stage_end
+                    .before(reset_player_scores)
+                    .before(reset_player_lives),
+                cleanup_game,
                 reset_multiplayer_mode,
+                reset_versus_mode,
+                reset_survival_mode,
+                reset_god_mode,
+                close_net_session,
+                reset_custom_level,
+                reset_generated_level,
+                stop_music,
+                play_start_menu_music,
             ),
         )
         .add_systems(
             Update,
-            (start_game, switch_multiplayer_mode).run_if(in_state(AppState::StartMenu)),
+            (
+                start_game,
+                switch_multiplayer_mode,
+                update_start_menu_cursor,
+                continue_saved_game,
+            )
+                .run_if(in_state(AppState::StartMenu)),
         )
         .add_systems(
             OnExit(AppState::StartMenu),
             (despawn_screen::<OnStartMenuScreen>,),
         )
-        .add_systems(OnEnter(AppState::Playing), (setup_levels,))
+        .add_systems(
+            OnEnter(AppState::Editor),
+            (
+                setup_editor,
+                reset_camera,
+                log_stage_end
+                    .before(reset_player_scores)
+                    .before(reset_player_lives),
+                cleanup_game,
+                stop_music,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                editor_keyboard_input,
+                editor_mouse_input,
+                update_editor_screen,
+            )
+                .chain()
+                .run_if(in_state(AppState::Editor)),
+        )
+        .add_systems(
+            OnExit(AppState::Editor),
+            (despawn_screen::<OnEditorScreen>,),
+        )
+        .add_systems(OnEnter(AppState::StageSelect), (setup_stage_select,))
+        .add_systems(
+            Update,
+            (
+                stage_select_input,
+                update_stage_thumbnails,
+                update_stage_select_screen,
+            )
+                .run_if(in_state(AppState::StageSelect)),
+        )
+        .add_systems(
+            OnExit(AppState::StageSelect),
+            (despawn_screen::<OnStageSelectScreen>,),
+        )
+        .add_systems(
+            OnEnter(AppState::Playing),
+            (
+                setup_levels,
+                setup_hud,
+                setup_minimap,
+                play_stage_music,
+                log_stage_start,
+            ),
+        )
+        .add_systems(
+            OnExit(AppState::Playing),
+            (stop_engine_sound, close_dev_console),
+        )
         .add_systems(
             Update,
             (
@@ -99,25 +371,179 @@ fn main() {
                 spawn_explosion,
                 animate_explosion,
                 handle_bullet_collision,
-                auto_switch_level,
+                check_stage_clear.run_if(not(survival_enabled)),
                 (
-                    auto_spawn_enemies,
+                    auto_spawn_enemies.run_if(not(survival_enabled)),
                     animate_enemies,
                     enemies_attack,
                     enemies_move,
                     handle_enemy_collision,
                     move_bullet,
-                    pause_game,
+                    pause_game.run_if(console_closed),
+                    unlock_reached_stage,
+                    update_engine_sound,
+                    update_hud,
+                    update_minimap,
                 ),
             )
                 .run_if(in_state(AppState::Playing)),
         )
-        .add_systems(Update, (unpause_game,).run_if(in_state(AppState::Paused)))
-        .add_systems(OnEnter(AppState::GameOver), (setup_game_over,))
+        .add_systems(
+            Update,
+            update_survival_waves
+                .after(update_tile_grid)
+                .run_if(in_state(AppState::Playing).and(survival_enabled)),
+        )
+        .add_systems(
+            Update,
+            read_player_inputs
+                .before(players_move)
+                .before(players_attack)
+                .run_if(in_state(AppState::Playing).and(console_closed)),
+        )
+        .add_systems(
+            Update,
+            (
+                dev_console_input,
+                execute_console_commands,
+                update_dev_console,
+            )
+                .chain()
+                .run_if(in_state(AppState::Playing).and(dev_tools_enabled)),
+        )
+        // 联机主机在所有状态下接收玩家2的输入并发送画面
+        .add_systems(
+            Update,
+            host_receive_input
+                .after(read_player_inputs)
+                .before(players_move)
+                .before(players_attack)
+                .run_if(not(in_state(AppState::Lobby))),
+        )
+        .add_systems(
+            PreUpdate,
+            advance_game_tick.run_if(in_state(AppState::Playing)),
+        )
+        .add_systems(PostUpdate, write_game_events)
+        .add_systems(
+            PostUpdate,
+            host_send_snapshot
+                .after(TransformSystem::TransformPropagate)
+                .after(VisibilitySystems::VisibilityPropagate),
+        )
+        .add_systems(OnEnter(AppState::Lobby), (setup_lobby,))
+        .add_systems(
+            Update,
+            (lobby_input, lobby_poll, update_lobby_screen)
+                .chain()
+                .run_if(in_state(AppState::Lobby)),
+        )
+        .add_systems(OnExit(AppState::Lobby), (despawn_screen::<OnLobbyScreen>,))
+        .add_systems(OnEnter(AppState::NetClient), (setup_net_client, setup_hud))
+        .add_systems(
+            Update,
+            (
+                client_send_input,
+                client_receive,
+                net_client_camera,
+                update_net_client_screen,
+                spawn_explosion,
+                animate_explosion,
+            )
+                .chain()
+                .run_if(in_state(AppState::NetClient)),
+        )
+        .add_systems(
+            OnExit(AppState::NetClient),
+            (
+                close_net_session,
+                cleanup_net_client,
+                reset_camera,
+                cleanup_explosions,
+                despawn_screen::<OnNetClientScreen>,
+                despawn_screen::<OnHudScreen>,
+            ),
+        )
+        .add_systems(OnEnter(AppState::Paused), (setup_pause_menu, pause_music))
+        .add_systems(Update, (pause_menu,).run_if(in_state(AppState::Paused)))
+        .add_systems(OnExit(AppState::Paused), (despawn_screen::<OnPauseScreen>,))
+        .add_systems(
+            OnTransition {
+                exited: AppState::Paused,
+                entered: AppState::StartMenu,
+            },
+            (save_on_quit.run_if(not(survival_enabled)),),
+        )
+        .add_systems(
+            OnEnter(AppState::Restarting),
+            (
+                log_stage_end
+                    .before(reset_player_scores)
+                    .before(reset_player_lives),
+                cleanup_stage,
+                stop_music,
+            ),
+        )
+        .add_systems(
+            Update,
+            (restart_stage,).run_if(in_state(AppState::Restarting)),
+        )
+        .add_systems(
+            OnEnter(AppState::StageClear),
+            (play_victory_jingle, log_stage_end),
+        )
+        .add_systems(
+            Update,
+            (auto_switch_level, animate_water, animate_explosion)
+                .run_if(in_state(AppState::StageClear)),
+        )
+        .add_systems(OnEnter(AppState::HighScores), (setup_high_scores_screen,))
+        .add_systems(
+            Update,
+            (high_scores_input,).run_if(in_state(AppState::HighScores)),
+        )
+        .add_systems(
+            OnExit(AppState::HighScores),
+            (despawn_screen::<OnHighScoresScreen>,),
+        )
+        .add_systems(
+            OnEnter(AppState::Victory),
+            (check_high_score, setup_victory.after(check_high_score)),
+        )
+        .add_systems(
+            Update,
+            (
+                initials_entry_input,
+                update_initials_entry_screen,
+                victory_input,
+                animate_water,
+            )
+                .run_if(in_state(AppState::Victory)),
+        )
+        .add_systems(
+            OnExit(AppState::Victory),
+            (
+                despawn_screen::<OnVictoryScreen>,
+                despawn_screen::<OnInitialsEntryScreen>,
+            ),
+        )
+        .add_systems(
+            OnEnter(AppState::GameOver),
+            (
+                setup_game_over,
+                log_stage_end,
+                check_high_score,
+                stop_music,
+                play_game_over_music,
+            ),
+        )
         .add_systems(
             Update,
             (
                 animate_game_over,
+                initials_entry_input,
+                update_initials_entry_screen,
+                game_over_menu,
                 animate_players,
                 animate_shield,
                 animate_water,
@@ -129,15 +555,15 @@ fn main() {
         )
         .add_systems(
             OnExit(AppState::GameOver),
-            (despawn_screen::<OnGameOverScreen>,),
+            (
+                despawn_screen::<OnGameOverScreen>,
+                despawn_screen::<OnInitialsEntryScreen>,
+                cleanup_stage,
+            ),
         )
         .run();
 }
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2d::default());
-}
-
 fn setup_rapier(mut rapier_config: Single<&mut RapierConfiguration>) {
     rapier_config.gravity = Vec2::ZERO;
 }
@@ -0,0 +1,463 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::Level;
+use bevy_rapier2d::prelude::*;
+
+use crate::cli::LaunchOptions;
+use crate::common::{
+    AnimationIndices, AnimationTimer, LEVEL_COLUMNS, LEVEL_ROWS, SPRITE_TREE_ORDER, TILE_SIZE,
+};
+use crate::level::{EnemiesMarker, LevelBounds, LevelItem, Player1Marker, Player2Marker};
+use crate::sprites::SpriteAssets;
+use crate::storage;
+
+// 编辑器保存的自定义关卡，和设置、排行榜一样放在存储目录（WASM为localStorage）
+pub const CUSTOM_LEVEL_FILE: &str = "custom_level.txt";
+
+// 自定义关卡地图格子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapTile {
+    #[default]
+    Empty,
+    StoneWall,
+    IronWall,
+    Water,
+    Tree,
+    Home,
+    Player1,
+    Player2,
+    Enemies,
+}
+
+impl MapTile {
+    pub const ALL: [MapTile; 9] = [
+        MapTile::Empty,
+        MapTile::StoneWall,
+        MapTile::IronWall,
+        MapTile::Water,
+        MapTile::Tree,
+        MapTile::Home,
+        MapTile::Player1,
+        MapTile::Player2,
+        MapTile::Enemies,
+    ];
+
+    pub fn to_char(self) -> char {
+        match self {
+            MapTile::Empty => '.',
+            MapTile::StoneWall => 'S',
+            MapTile::IronWall => 'I',
+            MapTile::Water => 'W',
+            MapTile::Tree => 'T',
+            MapTile::Home => 'H',
+            MapTile::Player1 => '1',
+            MapTile::Player2 => '2',
+            MapTile::Enemies => 'E',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<MapTile> {
+        MapTile::ALL.into_iter().find(|tile| tile.to_char() == c)
+    }
+
+    // ldtk实体名称对应的格子
+    pub fn from_name(name: &str) -> Option<MapTile> {
+        MapTile::ALL
+            .into_iter()
+            .find(|tile| *tile != MapTile::Empty && tile.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapTile::Empty => "Empty",
+            MapTile::StoneWall => "StoneWall",
+            MapTile::IronWall => "IronWall",
+            MapTile::Water => "Water",
+            MapTile::Tree => "Tree",
+            MapTile::Home => "Home",
+            MapTile::Player1 => "Player1",
+            MapTile::Player2 => "Player2",
+            MapTile::Enemies => "Enemies",
+        }
+    }
+
+    // 坦克无法通过的格子
+    pub fn is_solid(self) -> bool {
+        matches!(
+            self,
+            MapTile::StoneWall | MapTile::IronWall | MapTile::Water | MapTile::Home
+        )
+    }
+
+    // 寻路时不可通过的格子，砖墙可以被击毁所以视为可通过
+    pub fn blocks_path(self) -> bool {
+        matches!(self, MapTile::IronWall | MapTile::Water | MapTile::Home)
+    }
+
+    // 整张地图只允许出现一次的格子
+    pub fn is_unique(self) -> bool {
+        matches!(self, MapTile::Home | MapTile::Player1 | MapTile::Player2)
+    }
+
+    // map.bmp 中的sprite index
+    pub fn map_sprite_index(self) -> Option<usize> {
+        match self {
+            MapTile::StoneWall => Some(0),
+            MapTile::IronWall => Some(1),
+            MapTile::Tree => Some(2),
+            MapTile::Water => Some(3),
+            MapTile::Home => Some(5),
+            _ => None,
+        }
+    }
+}
+
+// 自定义关卡地图，第0行为地图最上方
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelMap {
+    pub width: i32,
+    pub height: i32,
+    pub tiles: Vec<MapTile>,
+}
+
+// 非空时使用该地图替代 levels.ldtk 中的关卡
+#[derive(Debug, Resource, Default)]
+pub struct CustomLevel(pub Option<LevelMap>);
+
+// 命令行指定的自定义关卡包，按顺序依次游玩
+#[derive(Debug, Resource, Default)]
+pub struct LevelPack {
+    pub maps: Vec<LevelMap>,
+    pub current: usize,
+}
+
+// 自定义关卡根实体
+#[derive(Debug, Component)]
+pub struct CustomLevelRoot;
+
+impl Default for LevelMap {
+    fn default() -> Self {
+        LevelMap::new(LEVEL_COLUMNS, LEVEL_ROWS)
+    }
+}
+
+impl LevelMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        LevelMap {
+            width,
+            height,
+            tiles: vec![MapTile::Empty; (width * height) as usize],
+        }
+    }
+
+    // 只有家、砖墙和出生点的初始地图
+    pub fn with_base(width: i32, height: i32) -> Self {
+        let mut map = LevelMap::new(width, height);
+        let home_col = width / 2;
+        let bottom = height - 1;
+        map.set(home_col, bottom, MapTile::Home);
+        for (col, row) in [
+            (home_col - 1, bottom),
+            (home_col + 1, bottom),
+            (home_col - 1, bottom - 1),
+            (home_col, bottom - 1),
+            (home_col + 1, bottom - 1),
+        ] {
+            map.set(col, row, MapTile::StoneWall);
+        }
+        map.set(home_col - 2, bottom, MapTile::Player1);
+        map.set(home_col + 2, bottom, MapTile::Player2);
+        map.set(0, 0, MapTile::Enemies);
+        map.set(home_col, 0, MapTile::Enemies);
+        map.set(width - 1, 0, MapTile::Enemies);
+        map
+    }
+
+    pub fn in_bounds(&self, col: i32, row: i32) -> bool {
+        col >= 0 && col < self.width && row >= 0 && row < self.height
+    }
+
+    pub fn get(&self, col: i32, row: i32) -> MapTile {
+        if !self.in_bounds(col, row) {
+            return MapTile::Empty;
+        }
+        self.tiles[(row * self.width + col) as usize]
+    }
+
+    pub fn set(&mut self, col: i32, row: i32, tile: MapTile) {
+        if !self.in_bounds(col, row) {
+            return;
+        }
+        if tile.is_unique() {
+            // 唯一格子重新放置时移除旧位置
+            for existing in self.tiles.iter_mut() {
+                if *existing == tile {
+                    *existing = MapTile::Empty;
+                }
+            }
+        }
+        self.tiles[(row * self.width + col) as usize] = tile;
+    }
+
+    // 所有指定类型格子的坐标(col, row)
+    pub fn positions(&self, tile: MapTile) -> Vec<IVec2> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| **t == tile)
+            .map(|(i, _)| IVec2::new(i as i32 % self.width, i as i32 / self.width))
+            .collect()
+    }
+
+    // 从起点出发可到达的格子
+    pub fn reachable_from(&self, start: IVec2) -> Vec<bool> {
+        let mut visited = vec![false; self.tiles.len()];
+        if !self.in_bounds(start.x, start.y) {
+            return visited;
+        }
+        let mut queue = std::collections::VecDeque::from([start]);
+        visited[(start.y * self.width + start.x) as usize] = true;
+        while let Some(pos) = queue.pop_front() {
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = pos + offset;
+                if !self.in_bounds(next.x, next.y) || self.get(next.x, next.y).blocks_path() {
+                    continue;
+                }
+                let index = (next.y * self.width + next.x) as usize;
+                if !visited[index] {
+                    visited[index] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        visited
+    }
+
+    // 无法到达家的出生点（敌人和玩家）
+    pub fn unreachable_spawns(&self) -> Vec<IVec2> {
+        let homes = self.positions(MapTile::Home);
+        let mut spawns = self.positions(MapTile::Enemies);
+        spawns.extend(self.positions(MapTile::Player1));
+        spawns.extend(self.positions(MapTile::Player2));
+        spawns
+            .into_iter()
+            .filter(|spawn| {
+                let reachable = self.reachable_from(*spawn);
+                !homes.iter().any(|home| {
+                    [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                        .iter()
+                        .map(|offset| *home + *offset)
+                        .any(|pos| {
+                            self.in_bounds(pos.x, pos.y)
+                                && reachable[(pos.y * self.width + pos.x) as usize]
+                        })
+                })
+            })
+            .collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                text.push(self.get(col, row).to_char());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<LevelMap, String> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.is_empty() {
+            return Err("level map is empty".to_string());
+        }
+        let width = lines[0].chars().count() as i32;
+        let height = lines.len() as i32;
+        let mut map = LevelMap::new(width, height);
+        for (row, line) in lines.iter().enumerate() {
+            if line.chars().count() as i32 != width {
+                return Err(format!(
+                    "line {} has {} columns, expected {}",
+                    row + 1,
+                    line.chars().count(),
+                    width
+                ));
+            }
+            for (col, c) in line.chars().enumerate() {
+                let Some(tile) = MapTile::from_char(c) else {
+                    return Err(format!(
+                        "unknown tile '{}' at line {}, column {}",
+                        c,
+                        row + 1,
+                        col + 1
+                    ));
+                };
+                map.tiles[row * width as usize + col] = tile;
+            }
+        }
+        Ok(map)
+    }
+
+    // 将ldtk关卡转换为地图，用于缩略图等只需要格子信息的场合
+    pub fn from_ldtk_level(level: &Level) -> LevelMap {
+        let tiles = |px: i32| (px + TILE_SIZE as i32 - 1) / TILE_SIZE as i32;
+        let mut map = LevelMap::new(tiles(level.px_wid), tiles(level.px_hei));
+        for layer in level.layer_instances.iter().flatten() {
+            for entity in &layer.entity_instances {
+                if let Some(tile) = MapTile::from_name(&entity.identifier) {
+                    map.set(entity.grid.x, entity.grid.y, tile);
+                }
+            }
+        }
+        map
+    }
+
+    pub fn load(path: &str) -> Result<LevelMap, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        LevelMap::from_text(&text)
+    }
+
+    // 从存储目录读取
+    pub fn load_stored(name: &str) -> Result<LevelMap, String> {
+        let text = storage::load_text(name).ok_or_else(|| format!("{} not found", name))?;
+        LevelMap::from_text(&text)
+    }
+
+    pub fn save_stored(&self, name: &str) -> Result<(), String> {
+        storage::save_text(name, &self.to_text())
+    }
+}
+
+// 格子中心相对关卡左下角的坐标，与ldtk实体的Transform一致
+pub fn tile_local_translation(map: &LevelMap, col: i32, row: i32) -> Vec3 {
+    Vec3::new(
+        col as f32 * TILE_SIZE + TILE_SIZE / 2.,
+        (map.height - 1 - row) as f32 * TILE_SIZE + TILE_SIZE / 2.,
+        0.,
+    )
+}
+
+// 按照ldtk实体相同的组件生成自定义关卡
+pub fn spawn_level_map(map: &LevelMap, commands: &mut Commands, sprite_assets: &SpriteAssets) {
+    let level_bounds = LevelBounds::of_map(map);
+    commands.insert_resource(level_bounds);
+    commands
+        .spawn((
+            CustomLevelRoot,
+            Transform::from_translation(level_bounds.offset()),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            for row in 0..map.height {
+                for col in 0..map.width {
+                    let tile = map.get(col, row);
+                    let mut translation = tile_local_translation(map, col, row);
+                    let sprite = tile.map_sprite_index().map(|index| Sprite {
+                        image: sprite_assets.map.clone(),
+                        texture_atlas: Some(TextureAtlas {
+                            index,
+                            layout: sprite_assets.map_layout.clone(),
+                        }),
+                        ..default()
+                    });
+                    match tile {
+                        MapTile::Empty => {}
+                        MapTile::StoneWall | MapTile::IronWall | MapTile::Home => {
+                            parent.spawn(wall_bundle(tile, translation, sprite_assets));
+                        }
+                        MapTile::Water => {
+                            parent.spawn((
+                                LevelItem::Water,
+                                sprite.unwrap(),
+                                Transform::from_translation(translation),
+                                Collider::cuboid(TILE_SIZE / 2., TILE_SIZE / 2.),
+                                RigidBody::Fixed,
+                                AnimationTimer(Timer::from_seconds(0.2, TimerMode::Repeating)),
+                                AnimationIndices { first: 3, last: 4 },
+                            ));
+                        }
+                        MapTile::Tree => {
+                            translation.z = SPRITE_TREE_ORDER;
+                            parent.spawn((
+                                LevelItem::Tree,
+                                sprite.unwrap(),
+                                Transform::from_translation(translation),
+                            ));
+                        }
+                        MapTile::Player1 => {
+                            parent.spawn((Player1Marker, Transform::from_translation(translation)));
+                        }
+                        MapTile::Player2 => {
+                            parent.spawn((Player2Marker, Transform::from_translation(translation)));
+                        }
+                        MapTile::Enemies => {
+                            parent.spawn((EnemiesMarker, Transform::from_translation(translation)));
+                        }
+                    }
+                }
+            }
+        });
+}
+
+// 砖墙、铁墙和家，生存模式修复家周围的砖墙时也使用
+pub fn wall_bundle(tile: MapTile, translation: Vec3, sprite_assets: &SpriteAssets) -> impl Bundle {
+    (
+        level_item(tile),
+        Sprite {
+            image: sprite_assets.map.clone(),
+            texture_atlas: Some(TextureAtlas {
+                index: tile.map_sprite_index().unwrap_or_default(),
+                layout: sprite_assets.map_layout.clone(),
+            }),
+            ..default()
+        },
+        Transform::from_translation(translation),
+        Collider::cuboid(TILE_SIZE / 2., TILE_SIZE / 2.),
+        RigidBody::Fixed,
+    )
+}
+
+fn level_item(tile: MapTile) -> LevelItem {
+    match tile {
+        MapTile::StoneWall => LevelItem::StoneWall,
+        MapTile::IronWall => LevelItem::IronWall,
+        MapTile::Water => LevelItem::Water,
+        MapTile::Tree => LevelItem::Tree,
+        MapTile::Home => LevelItem::Home,
+        _ => LevelItem::None,
+    }
+}
+
+// 地图元素对应的格子，小地图使用
+pub fn map_tile(level_item: &LevelItem) -> MapTile {
+    match level_item {
+        LevelItem::StoneWall => MapTile::StoneWall,
+        LevelItem::IronWall => MapTile::IronWall,
+        LevelItem::Water => MapTile::Water,
+        LevelItem::Tree => MapTile::Tree,
+        LevelItem::Home => MapTile::Home,
+        LevelItem::None => MapTile::Empty,
+    }
+}
+
+pub fn reset_custom_level(
+    mut custom_level: ResMut<CustomLevel>,
+    mut level_pack: ResMut<LevelPack>,
+    launch_options: Res<LaunchOptions>,
+) {
+    level_pack.current = launch_options.start_level();
+    custom_level.0 = level_pack.maps.get(level_pack.current).cloned();
+}
+
+pub fn cleanup_custom_level(
+    mut commands: Commands,
+    q_custom_level: Query<Entity, With<CustomLevelRoot>>,
+) {
+    for entity in &q_custom_level {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::bullet::Bullet;
+use crate::common::{self, TILE_SIZE};
+use crate::enemy::Enemy;
+use crate::level::{LevelBounds, LevelItem};
+use crate::map::{map_tile, MapTile};
+use crate::player::{Born, PlayerNo};
+use crate::tile_grid::TileGrid;
+use crate::ui::OnHudScreen;
+
+// 小地图上每个格子的大小（UI像素）
+const MINIMAP_TILE_PIXELS: f32 = 4.0;
+const MINIMAP_EMPTY_COLOR: Color = Color::srgba(0., 0., 0., 0.6);
+// 家受到威胁时在白色和品红之间闪烁，和红色的敌人区分开
+const HOME_THREAT_COLOR: Color = Color::srgb(1.0, 0.0, 1.0);
+// 敌人距离家多少格以内视为威胁
+const HOME_THREAT_TILES: f32 = 5.0;
+const HOME_FLASH_SECS: f32 = 0.25;
+
+#[derive(Component)]
+pub struct Minimap;
+
+// 小地图配色：家、双方玩家和敌人使用醒目且互不相同的颜色，地形颜色偏暗
+fn minimap_color(tile: MapTile) -> Color {
+    match tile {
+        MapTile::Empty => MINIMAP_EMPTY_COLOR,
+        MapTile::StoneWall => Color::srgb(0.55, 0.3, 0.15),
+        MapTile::IronWall => Color::srgb(0.5, 0.5, 0.5),
+        MapTile::Water => Color::srgb(0.1, 0.15, 0.5),
+        MapTile::Tree => Color::srgb(0.1, 0.45, 0.1),
+        MapTile::Home => Color::WHITE,
+        MapTile::Player1 => Color::srgb(1.0, 0.9, 0.0),
+        MapTile::Player2 => Color::srgb(0.0, 0.9, 1.0),
+        MapTile::Enemies => Color::srgb(1.0, 0.1, 0.1),
+    }
+}
+
+// 敌人靠近家，或者有敌人子弹正朝家飞去
+pub fn home_under_threat(
+    home: Vec2,
+    enemies: &[Vec2],
+    enemy_bullets: &[(Vec2, common::Direction)],
+) -> bool {
+    if enemies
+        .iter()
+        .any(|enemy| enemy.distance(home) <= HOME_THREAT_TILES * TILE_SIZE)
+    {
+        return true;
+    }
+    enemy_bullets.iter().any(|(pos, direction)| {
+        let to_home = home - *pos;
+        // 沿飞行方向的距离和偏离飞行方向的距离
+        let (along, across) = match direction {
+            common::Direction::Up => (to_home.y, to_home.x),
+            common::Direction::Down => (-to_home.y, to_home.x),
+            common::Direction::Right => (to_home.x, to_home.y),
+            common::Direction::Left => (-to_home.x, to_home.y),
+        };
+        along > 0. && across.abs() < TILE_SIZE
+    })
+}
+
+fn minimap_image(level_bounds: &LevelBounds) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: level_bounds.columns as u32,
+            height: level_bounds.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &MINIMAP_EMPTY_COLOR.to_srgba().to_u8_array(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+    );
+    // 每格一个像素，放大显示时保持方块边缘清晰
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+fn minimap_node(level_bounds: &LevelBounds) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(30.),
+        right: Val::Px(30.),
+        width: Val::Px(level_bounds.columns as f32 * MINIMAP_TILE_PIXELS),
+        height: Val::Px(level_bounds.rows as f32 * MINIMAP_TILE_PIXELS),
+        ..default()
+    }
+}
+
+pub fn setup_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    level_bounds: Res<LevelBounds>,
+    q_minimap: Query<(), With<Minimap>>,
+) {
+    // 从暂停恢复时小地图仍在
+    if !q_minimap.is_empty() {
+        return;
+    }
+    commands.spawn((
+        minimap_node(&level_bounds),
+        ImageNode::new(images.add(minimap_image(&level_bounds))),
+        OnHudScreen,
+        Minimap,
+    ));
+}
+
+// 每帧根据地图元素格子、坦克位置和家的状态重绘小地图
+pub fn update_minimap(
+    time: Res<Time>,
+    level_bounds: Res<LevelBounds>,
+    tile_grid: Res<TileGrid>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_minimap: Query<(&ImageNode, &mut Node), With<Minimap>>,
+    q_enemies: Query<&Transform, With<Enemy>>,
+    q_players: Query<(&PlayerNo, &Transform), Without<Born>>,
+    q_bullets: Query<(&Bullet, &Transform, &common::Direction)>,
+) {
+    let Ok((image_node, mut node)) = q_minimap.get_single_mut() else {
+        return;
+    };
+    let Some(image) = images.get_mut(&image_node.image) else {
+        return;
+    };
+    if level_bounds.is_changed() {
+        *image = minimap_image(&level_bounds);
+        *node = minimap_node(&level_bounds);
+    }
+
+    let empty = MINIMAP_EMPTY_COLOR.to_srgba().to_u8_array();
+    for pixel in image.data.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&empty);
+    }
+
+    let offset = level_bounds.offset().truncate();
+    let mut set_pixel = |pos: Vec2, color: Color| {
+        let local = pos - offset;
+        let col = (local.x / TILE_SIZE).floor() as i32;
+        let row = level_bounds.rows - 1 - (local.y / TILE_SIZE).floor() as i32;
+        if col < 0 || row < 0 || col >= level_bounds.columns || row >= level_bounds.rows {
+            return;
+        }
+        let index = ((row * level_bounds.columns + col) * 4) as usize;
+        if let Some(data) = image.data.get_mut(index..index + 4) {
+            data.copy_from_slice(&color.to_srgba().to_u8_array());
+        }
+    };
+
+    let enemies: Vec<Vec2> = q_enemies
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let enemy_bullets: Vec<(Vec2, common::Direction)> = q_bullets
+        .iter()
+        .filter(|(bullet, _, _)| **bullet == Bullet::Enemy)
+        .map(|(_, transform, direction)| (transform.translation.truncate(), *direction))
+        .collect();
+    let flash_on = ((time.elapsed_secs() / HOME_FLASH_SECS) as u32).is_multiple_of(2);
+    for (level_item, pos) in tile_grid.iter() {
+        let color = match level_item {
+            LevelItem::None => continue,
+            LevelItem::Home if flash_on && home_under_threat(pos, &enemies, &enemy_bullets) => {
+                HOME_THREAT_COLOR
+            }
+            _ => minimap_color(map_tile(level_item)),
+        };
+        set_pixel(pos, color);
+    }
+    for enemy in &enemies {
+        set_pixel(*enemy, minimap_color(MapTile::Enemies));
+    }
+    for (player_no, transform) in &q_players {
+        let tile = if player_no.0 == 2 {
+            MapTile::Player2
+        } else {
+            MapTile::Player1
+        };
+        set_pixel(transform.translation.truncate(), minimap_color(tile));
+    }
+}
@@ -0,0 +1,1177 @@
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{Sound, SoundEvent};
+use crate::bullet::{ExplosionEvent, ExplosionType};
+use crate::camera::{camera_view, level_overview, GameCamera, VIEW_SIZE};
+use crate::cli::LaunchOptions;
+use crate::common::{AppState, MultiplayerMode, VersusMode, ENEMIES_PER_LEVEL};
+use crate::enemy::{Enemy, LevelSpawnedEnemies};
+use crate::level::{stage_number, LevelBounds};
+use crate::locale::{Locale, LocalizedText, TextKey};
+use crate::map::{CustomLevel, LevelPack};
+use crate::player::{
+    PlayerInput, PlayerInputs, PlayerLives, PlayerNo, PlayerScores, RemotePlayer2, PLAYER1_KEYS,
+    PLAYER2_KEYS,
+};
+use crate::ui::{hud_text, stage_hud_text, HudText, OnHudScreen, MENU_SELECTED_COLOR};
+
+pub const DEFAULT_PORT: u16 = 7878;
+const PROTOCOL_VERSION: u32 = 3;
+// 超过这个时间没有收到对方的数据视为断线（秒）
+const NET_TIMEOUT_SECS: f32 = 3.0;
+const CONNECT_TIMEOUT_SECS: f32 = 5.0;
+const HELLO_INTERVAL_SECS: f32 = 0.5;
+// 每隔多少帧发送一次完整画面，其余帧只发送有变化的精灵
+const KEYFRAME_INTERVAL: u64 = 60;
+// 拆分数据包，避免超过UDP数据包大小
+const SPRITES_PER_PACKET: usize = 150;
+const MAX_PACKET_SIZE: usize = 65507;
+const MAX_ADDRESS_LEN: usize = 40;
+// 观众跟随玩家时的镜头缩放
+const FOLLOW_CAMERA_SCALE: f32 = 0.5;
+
+// 主机和客户端之间的消息，使用json编码，每个UDP数据包一条
+#[derive(Debug, Serialize, Deserialize)]
+pub enum NetMessage {
+    // 观众只接收画面，不发送操作
+    Hello { version: u32, spectator: bool },
+    Welcome,
+    Input(PlayerInput),
+    Snapshot(Snapshot),
+    Bye,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NetGameState {
+    #[default]
+    Playing,
+    Paused,
+    StageClear,
+    GameOver,
+}
+
+// 主机的HUD数据和当前状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetStatus {
+    pub state: NetGameState,
+    pub stage: usize,
+    pub enemies: i32,
+    pub lives: [i8; 2],
+    pub scores: [u32; 2],
+    // 关卡的列数和行数，客户端据此生成场地边界和限制镜头范围
+    pub level_size: [i32; 2],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetSprite {
+    pub id: u64,
+    pub image: String,
+    pub rect: Option<[f32; 4]>,
+    pub size: Option<[f32; 2]>,
+    pub color: [f32; 4],
+    pub flip: [bool; 2],
+    pub translation: [f32; 3],
+    pub angle: f32,
+    // 玩家坦克的编号，观众跟随视角使用
+    pub player: Option<u32>,
+    pub scale: [f32; 2],
+    pub visible: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetExplosion {
+    pub pos: [f32; 3],
+    pub big: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub frame: u64,
+    pub status: NetStatus,
+    pub sprites: Vec<NetSprite>,
+    pub removed: Vec<u64>,
+    pub explosions: Vec<NetExplosion>,
+    pub sounds: Vec<Sound>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetRole {
+    Host,
+    Client,
+}
+
+// 联机会话，主机运行游戏逻辑，客户端只发送输入并显示主机的画面
+#[derive(Debug, Resource)]
+pub struct NetSession {
+    pub role: NetRole,
+    pub spectator: bool,
+    socket: UdpSocket,
+    pub peer: Option<SocketAddr>,
+    // 主机上的观众和最后一次收到数据的时间
+    spectators: HashMap<SocketAddr, f32>,
+    started: f32,
+    last_received: f32,
+    last_hello: f32,
+    frame: u64,
+    // 客户端上已有的精灵，用于发送增量和删除
+    sent_sprites: HashSet<u64>,
+}
+
+impl NetSession {
+    pub fn host(port: u16, now: f32) -> Result<Self, String> {
+        let socket =
+            UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("port {}: {}", port, e))?;
+        Self::new(NetRole::Host, false, socket, None, now)
+    }
+
+    pub fn join(address: &str, spectator: bool, now: f32) -> Result<Self, String> {
+        let peer = address
+            .to_socket_addrs()
+            .map_err(|e| format!("{}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("{}: address not found", address))?;
+        let local = if peer.is_ipv6() {
+            "[::]:0"
+        } else {
+            "0.0.0.0:0"
+        };
+        let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        Self::new(NetRole::Client, spectator, socket, Some(peer), now)
+    }
+
+    fn new(
+        role: NetRole,
+        spectator: bool,
+        socket: UdpSocket,
+        peer: Option<SocketAddr>,
+        now: f32,
+    ) -> Result<Self, String> {
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(NetSession {
+            role,
+            spectator,
+            socket,
+            peer,
+            spectators: HashMap::new(),
+            started: now,
+            last_received: now,
+            last_hello: f32::NEG_INFINITY,
+            frame: 0,
+            sent_sprites: HashSet::new(),
+        })
+    }
+
+    fn encode(message: &NetMessage) -> Option<Vec<u8>> {
+        let data = match serde_json::to_vec(message) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to encode net message: {}", e);
+                return None;
+            }
+        };
+        if data.len() > MAX_PACKET_SIZE {
+            warn!("Net message too large: {} bytes", data.len());
+            return None;
+        }
+        Some(data)
+    }
+
+    fn send_to(&self, data: &[u8], address: SocketAddr) {
+        if let Err(e) = self.socket.send_to(data, address) {
+            debug!("Failed to send to {}: {}", address, e);
+        }
+    }
+
+    pub fn send(&self, message: &NetMessage) {
+        if let (Some(peer), Some(data)) = (self.peer, Self::encode(message)) {
+            self.send_to(&data, peer);
+        }
+    }
+
+    // 发送给玩家2和所有观众
+    pub fn broadcast(&self, message: &NetMessage) {
+        let Some(data) = Self::encode(message) else {
+            return;
+        };
+        for address in self.peer.iter().chain(self.spectators.keys()) {
+            self.send_to(&data, *address);
+        }
+    }
+
+    // 读取所有已到达的消息，只有来自对方的数据才刷新超时
+    fn receive(&mut self, now: f32) -> Vec<(SocketAddr, NetMessage)> {
+        let mut messages = Vec::new();
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, address)) => match serde_json::from_slice(&buffer[..len]) {
+                    Ok(message) => {
+                        if self.peer == Some(address) {
+                            self.last_received = now;
+                        } else if let Some(last_received) = self.spectators.get_mut(&address) {
+                            *last_received = now;
+                        }
+                        messages.push((address, message));
+                    }
+                    Err(e) => debug!("Invalid net message from {}: {}", address, e),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    debug!("Failed to receive: {}", e);
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    fn timed_out(&self, now: f32) -> bool {
+        now - self.last_received > NET_TIMEOUT_SECS
+    }
+
+    // 观众可以随时加入，重复的Hello用于保持连接
+    fn handle_spectator(&mut self, address: SocketAddr, message: &NetMessage, now: f32) -> bool {
+        match message {
+            NetMessage::Hello {
+                version: PROTOCOL_VERSION,
+                spectator: true,
+            } => {
+                if self.spectators.insert(address, now).is_none() {
+                    info!("Spectator joined from {}", address);
+                }
+                if let Some(data) = Self::encode(&NetMessage::Welcome) {
+                    self.send_to(&data, address);
+                }
+                true
+            }
+            NetMessage::Bye if self.spectators.remove(&address).is_some() => {
+                info!("Spectator {} left", address);
+                true
+            }
+            _ => self.spectators.contains_key(&address),
+        }
+    }
+
+    fn drop_timed_out_spectators(&mut self, now: f32) {
+        self.spectators.retain(|address, last_received| {
+            let alive = now - *last_received <= NET_TIMEOUT_SECS;
+            if !alive {
+                info!("Spectator {} timed out", address);
+            }
+            alive
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyItem {
+    Host,
+    Join,
+    Watch,
+    Mode,
+    Back,
+}
+
+impl LobbyItem {
+    pub const ALL: [LobbyItem; 5] = [
+        LobbyItem::Host,
+        LobbyItem::Join,
+        LobbyItem::Watch,
+        LobbyItem::Mode,
+        LobbyItem::Back,
+    ];
+
+    fn text(self, lobby: &Lobby, versus_mode: &VersusMode, locale: &Locale) -> String {
+        match self {
+            LobbyItem::Host => locale.tr(TextKey::HostGame).to_string(),
+            LobbyItem::Join => format!("{} {}", locale.tr(TextKey::JoinGame), lobby.address),
+            LobbyItem::Watch => format!("{} {}", locale.tr(TextKey::Watch), lobby.address),
+            LobbyItem::Mode => format!(
+                "{}  < {} >",
+                locale.tr(TextKey::Mode),
+                locale.tr(if versus_mode.0 {
+                    TextKey::Versus
+                } else {
+                    TextKey::CoOp
+                })
+            ),
+            LobbyItem::Back => locale.tr(TextKey::Back).to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct Lobby {
+    pub cursor: usize,
+    pub address: String,
+    // 等待连接、连接超时等提示和附加信息
+    pub status: Option<(TextKey, String)>,
+    auto_started: bool,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby {
+            cursor: 0,
+            address: format!("127.0.0.1:{}", DEFAULT_PORT),
+            status: None,
+            auto_started: false,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OnLobbyScreen;
+#[derive(Component)]
+pub struct LobbyText(pub LobbyItem);
+#[derive(Component)]
+pub struct LobbyStatusText;
+
+fn start_host(commands: &mut Commands, lobby: &mut Lobby, port: u16, now: f32) {
+    match NetSession::host(port, now) {
+        Ok(session) => {
+            info!("Hosting LAN game on port {}", port);
+            commands.insert_resource(session);
+            lobby.status = Some((TextKey::WaitingForPlayer, port.to_string()));
+        }
+        Err(e) => {
+            error!("Failed to host LAN game: {}", e);
+            lobby.status = Some((TextKey::NetError, e));
+        }
+    }
+}
+
+fn start_join(commands: &mut Commands, lobby: &mut Lobby, spectator: bool, now: f32) {
+    match NetSession::join(&lobby.address, spectator, now) {
+        Ok(session) => {
+            info!(
+                "Joining LAN game at {}{}",
+                lobby.address,
+                if spectator { " as spectator" } else { "" }
+            );
+            commands.insert_resource(session);
+            lobby.status = Some((TextKey::Connecting, lobby.address.clone()));
+        }
+        Err(e) => {
+            error!("Failed to join LAN game: {}", e);
+            lobby.status = Some((TextKey::NetError, e));
+        }
+    }
+}
+
+pub fn reset_versus_mode(mut versus_mode: ResMut<VersusMode>, launch_options: Res<LaunchOptions>) {
+    versus_mode.0 = launch_options.versus;
+}
+
+pub fn setup_lobby(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    launch_options: Res<LaunchOptions>,
+    mut lobby: ResMut<Lobby>,
+    time: Res<Time<Real>>,
+) {
+    lobby.cursor = 0;
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            OnLobbyScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::LanGame)),
+                locale.text_font(36.),
+                LocalizedText(TextKey::LanGame),
+                Node {
+                    margin: UiRect::bottom(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+            for item in LobbyItem::ALL {
+                parent.spawn((
+                    Text::default(),
+                    locale.text_font(22.),
+                    TextColor(Color::WHITE),
+                    LobbyText(item),
+                ));
+            }
+            parent.spawn((
+                Text::default(),
+                locale.text_font(18.),
+                TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                LobbyStatusText,
+                Node {
+                    margin: UiRect::top(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                Text::new(locale.tr(TextKey::LobbyHelp)),
+                locale.text_font(14.),
+                TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                LocalizedText(TextKey::LobbyHelp),
+            ));
+        });
+    // 命令行参数只在第一次进入大厅时自动创建主机或加入
+    if !lobby.auto_started {
+        lobby.auto_started = true;
+        let now = time.elapsed_secs();
+        if let Some(address) = &launch_options.join {
+            lobby.address.clone_from(address);
+            start_join(&mut commands, &mut lobby, false, now);
+        } else if let Some(address) = &launch_options.spectate {
+            lobby.address.clone_from(address);
+            start_join(&mut commands, &mut lobby, true, now);
+        } else if launch_options.host {
+            start_host(&mut commands, &mut lobby, launch_options.port(), now);
+        }
+    }
+}
+
+pub fn lobby_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut keyboard_er: EventReader<KeyboardInput>,
+    session: Option<Res<NetSession>>,
+    launch_options: Res<LaunchOptions>,
+    mut lobby: ResMut<Lobby>,
+    mut versus_mode: ResMut<VersusMode>,
+    time: Res<Time<Real>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+) {
+    let typed: Vec<Key> = keyboard_er
+        .read()
+        .filter(|event| event.state.is_pressed())
+        .map(|event| event.logical_key.clone())
+        .collect();
+    // 等待连接时只能取消
+    if let Some(session) = session {
+        if keyboard_input.just_pressed(KeyCode::Escape) {
+            info!("Cancel LAN game");
+            session.broadcast(&NetMessage::Bye);
+            commands.remove_resource::<NetSession>();
+            lobby.status = None;
+        }
+        return;
+    }
+    let items = LobbyItem::ALL.len();
+    let item = LobbyItem::ALL[lobby.cursor];
+    let now = time.elapsed_secs();
+    let confirm = keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]);
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        lobby.cursor = (lobby.cursor + 1) % items;
+    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        lobby.cursor = (lobby.cursor + items - 1) % items;
+    } else if keyboard_input.just_pressed(KeyCode::Escape) || (item == LobbyItem::Back && confirm) {
+        lobby.status = None;
+        app_state.set(AppState::StartMenu);
+        return;
+    } else if item == LobbyItem::Mode
+        && (confirm || keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::ArrowRight]))
+    {
+        versus_mode.0 = !versus_mode.0;
+    } else if item == LobbyItem::Host && confirm {
+        start_host(&mut commands, &mut lobby, launch_options.port(), now);
+    } else if matches!(item, LobbyItem::Join | LobbyItem::Watch) && confirm {
+        start_join(&mut commands, &mut lobby, item == LobbyItem::Watch, now);
+    } else if matches!(item, LobbyItem::Join | LobbyItem::Watch) {
+        // 选中加入或观战时直接输入地址
+        for key in typed {
+            match key {
+                Key::Character(text) => {
+                    for c in text.chars() {
+                        if (c.is_ascii_alphanumeric() || ".:-[]".contains(c))
+                            && lobby.address.len() < MAX_ADDRESS_LEN
+                        {
+                            lobby.address.push(c);
+                        }
+                    }
+                }
+                Key::Backspace => {
+                    lobby.address.pop();
+                }
+                _ => {}
+            }
+        }
+        return;
+    } else {
+        return;
+    }
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
+
+// 主机等待玩家2加入，客户端等待主机应答
+pub fn lobby_poll(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    mut lobby: ResMut<Lobby>,
+    mut multiplayer_mode: ResMut<MultiplayerMode>,
+    mut player_inputs: ResMut<PlayerInputs>,
+    time: Res<Time<Real>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let messages = session.receive(now);
+    match session.role {
+        NetRole::Host => {
+            for (address, message) in messages {
+                if session.handle_spectator(address, &message, now) {
+                    continue;
+                }
+                let NetMessage::Hello { version, .. } = message else {
+                    continue;
+                };
+                if version != PROTOCOL_VERSION {
+                    warn!("Ignore {} with protocol version {}", address, version);
+                    continue;
+                }
+                info!("Player 2 joined from {}", address);
+                session.peer = Some(address);
+                session.last_received = now;
+                session.send(&NetMessage::Welcome);
+                *multiplayer_mode = MultiplayerMode::TwoPlayers;
+                player_inputs.current[1] = PlayerInput::default();
+                commands.insert_resource(RemotePlayer2);
+                lobby.status = None;
+                app_state.set(AppState::Playing);
+                break;
+            }
+            session.drop_timed_out_spectators(now);
+        }
+        NetRole::Client => {
+            if messages.iter().any(|(address, message)| {
+                session.peer == Some(*address) && matches!(message, NetMessage::Welcome)
+            }) {
+                info!("Joined LAN game at {}", lobby.address);
+                lobby.status = None;
+                app_state.set(AppState::NetClient);
+            } else if now - session.started > CONNECT_TIMEOUT_SECS {
+                warn!("Connection to {} timed out", lobby.address);
+                commands.remove_resource::<NetSession>();
+                lobby.status = Some((TextKey::ConnectionTimedOut, lobby.address.clone()));
+            } else if now - session.last_hello >= HELLO_INTERVAL_SECS {
+                session.last_hello = now;
+                let spectator = session.spectator;
+                session.send(&NetMessage::Hello {
+                    version: PROTOCOL_VERSION,
+                    spectator,
+                });
+            }
+        }
+    }
+}
+
+pub fn update_lobby_screen(
+    lobby: Res<Lobby>,
+    versus_mode: Res<VersusMode>,
+    locale: Res<Locale>,
+    mut q_items: Query<(&LobbyText, &mut Text, &mut TextColor), Without<LobbyStatusText>>,
+    mut q_status: Query<&mut Text, With<LobbyStatusText>>,
+) {
+    for (lobby_text, mut text, mut text_color) in &mut q_items {
+        text.0 = lobby_text.0.text(&lobby, &versus_mode, &locale);
+        text_color.0 = if LobbyItem::ALL[lobby.cursor] == lobby_text.0 {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+    let status = match &lobby.status {
+        Some((key, detail)) => format!("{} {}", locale.tr(*key), detail),
+        None => String::new(),
+    };
+    for mut text in &mut q_status {
+        if text.0 != status {
+            text.0.clone_from(&status);
+        }
+    }
+}
+
+// 离开联机时通知对方
+pub fn close_net_session(mut commands: Commands, session: Option<Res<NetSession>>) {
+    if let Some(session) = session {
+        session.broadcast(&NetMessage::Bye);
+        commands.remove_resource::<NetSession>();
+        commands.remove_resource::<RemotePlayer2>();
+    }
+}
+
+// 主机读取玩家2的输入，玩家2断开后由本机键盘接管，观众仍然可以继续观看
+pub fn host_receive_input(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    mut player_inputs: ResMut<PlayerInputs>,
+    locale: Res<Locale>,
+    time: Res<Time<Real>>,
+) {
+    let Some(mut session) = session.filter(|session| session.role == NetRole::Host) else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let peer = session.peer;
+    let mut left = false;
+    for (address, message) in session.receive(now) {
+        if session.handle_spectator(address, &message, now) || Some(address) != peer {
+            continue;
+        }
+        match message {
+            NetMessage::Input(input) => player_inputs.current[1] = input,
+            // 客户端没有收到Welcome时会重发Hello
+            NetMessage::Hello { .. } => session.send(&NetMessage::Welcome),
+            NetMessage::Bye => left = true,
+            _ => {}
+        }
+    }
+    session.drop_timed_out_spectators(now);
+    if peer.is_none() || (!left && !session.timed_out(now)) {
+        return;
+    }
+    warn!("Player 2 disconnected");
+    session.peer = None;
+    commands.remove_resource::<RemotePlayer2>();
+    player_inputs.current[1] = PlayerInput::default();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnHudScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::Player2Left)),
+                locale.text_font(18.),
+                TextColor(MENU_SELECTED_COLOR),
+                LocalizedText(TextKey::Player2Left),
+            ));
+        });
+}
+
+fn net_sprite(
+    id: u64,
+    image: String,
+    sprite: &Sprite,
+    transform: &GlobalTransform,
+    visible: bool,
+    player: Option<u32>,
+    atlas_layouts: &Assets<TextureAtlasLayout>,
+) -> NetSprite {
+    // 图集转换成贴图中的矩形区域，客户端不需要图集布局
+    let rect = sprite
+        .texture_atlas
+        .as_ref()
+        .and_then(|atlas| {
+            atlas_layouts
+                .get(&atlas.layout)
+                .and_then(|layout| layout.textures.get(atlas.index))
+                .map(|rect| rect.as_rect())
+        })
+        .or(sprite.rect);
+    let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+    NetSprite {
+        id,
+        image,
+        rect: rect.map(|rect| [rect.min.x, rect.min.y, rect.max.x, rect.max.y]),
+        size: sprite.custom_size.map(|size| size.to_array()),
+        color: sprite.color.to_srgba().to_f32_array(),
+        flip: [sprite.flip_x, sprite.flip_y],
+        translation: translation.to_array(),
+        angle: rotation.to_euler(EulerRot::ZYX).0,
+        scale: [scale.x, scale.y],
+        player,
+        visible,
+    }
+}
+
+// 在变换和可见性计算完成后发送本帧画面
+pub fn host_send_snapshot(
+    session: Option<ResMut<NetSession>>,
+    app_state: Res<State<AppState>>,
+    q_sprites: Query<(
+        Entity,
+        Ref<Sprite>,
+        Ref<GlobalTransform>,
+        Ref<InheritedVisibility>,
+        Option<&PlayerNo>,
+    )>,
+    atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    asset_server: Res<AssetServer>,
+    mut explosion_er: EventReader<ExplosionEvent>,
+    mut sound_er: EventReader<SoundEvent>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    level_pack: Res<LevelPack>,
+    level_spawned_enemies: Res<LevelSpawnedEnemies>,
+    q_enemies: Query<(), With<Enemy>>,
+    player_lives: Res<PlayerLives>,
+    player_scores: Res<PlayerScores>,
+    level_bounds: Res<LevelBounds>,
+) {
+    let mut explosions: Vec<NetExplosion> = explosion_er
+        .read()
+        .map(|explosion| NetExplosion {
+            pos: explosion.pos.to_array(),
+            big: explosion.explosion_type == ExplosionType::BigExplosion,
+        })
+        .collect();
+    // 爆炸音效由客户端生成爆炸时自己播放
+    let mut sounds: Vec<Sound> = sound_er
+        .read()
+        .map(|event| event.0)
+        .filter(|sound| !matches!(sound, Sound::BigExplosion | Sound::BulletExplosion))
+        .collect();
+    let Some(mut session) = session.filter(|session| {
+        session.role == NetRole::Host && (session.peer.is_some() || !session.spectators.is_empty())
+    }) else {
+        return;
+    };
+    let state = match app_state.get() {
+        // 大厅中还没有开始对局
+        AppState::Lobby => return,
+        AppState::Paused | AppState::Options => NetGameState::Paused,
+        AppState::StageClear => NetGameState::StageClear,
+        AppState::GameOver => NetGameState::GameOver,
+        _ => NetGameState::Playing,
+    };
+    session.frame += 1;
+    let frame = session.frame;
+    let keyframe = frame % KEYFRAME_INTERVAL == 1;
+
+    let mut current = HashSet::new();
+    let mut sprites = Vec::new();
+    for (entity, sprite, transform, visibility, player_no) in &q_sprites {
+        // 爆炸等运行时生成的贴图没有路径，由客户端根据爆炸事件生成
+        let Some(path) = asset_server.get_path(sprite.image.id()) else {
+            continue;
+        };
+        let id = entity.to_bits();
+        current.insert(id);
+        let changed = sprite.is_changed() || transform.is_changed() || visibility.is_changed();
+        if keyframe || changed || !session.sent_sprites.contains(&id) {
+            sprites.push(net_sprite(
+                id,
+                path.to_string(),
+                &sprite,
+                &transform,
+                visibility.get(),
+                player_no.map(|player_no| player_no.0),
+                &atlas_layouts,
+            ));
+        }
+    }
+    let mut removed: Vec<u64> = session.sent_sprites.difference(&current).copied().collect();
+    session.sent_sprites = current;
+
+    let status = NetStatus {
+        state,
+        stage: stage_number(&level_selection, &custom_level, &level_pack),
+        enemies: ENEMIES_PER_LEVEL - level_spawned_enemies.0 + q_enemies.iter().len() as i32,
+        lives: [player_lives.player1, player_lives.player2],
+        scores: [player_scores.player1, player_scores.player2],
+        level_size: [level_bounds.columns, level_bounds.rows],
+    };
+    let mut chunks: Vec<Vec<NetSprite>> = sprites
+        .chunks(SPRITES_PER_PACKET)
+        .map(<[NetSprite]>::to_vec)
+        .collect();
+    if chunks.is_empty() {
+        chunks.push(Vec::new());
+    }
+    // 只有第一个数据包携带删除、爆炸和音效
+    for chunk in chunks {
+        session.broadcast(&NetMessage::Snapshot(Snapshot {
+            frame,
+            status: status.clone(),
+            sprites: chunk,
+            removed: std::mem::take(&mut removed),
+            explosions: std::mem::take(&mut explosions),
+            sounds: std::mem::take(&mut sounds),
+        }));
+    }
+}
+
+// 客户端上镜像主机画面的精灵
+#[derive(Component)]
+pub struct MirroredSprite {
+    id: u64,
+    last_frame: u64,
+    player: Option<u32>,
+}
+
+#[derive(Component)]
+pub struct OnNetClientScreen;
+#[derive(Component)]
+pub struct NetStateText;
+#[derive(Component)]
+pub struct SpectatorText;
+
+#[derive(Debug, Resource, Default)]
+pub struct NetClientView {
+    sprites: HashMap<u64, Entity>,
+    images: HashMap<String, Handle<Image>>,
+    frame: u64,
+    status: NetStatus,
+    // 观众跟随的玩家，None为全景
+    follow: Option<u32>,
+}
+
+pub fn setup_net_client(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    session: Option<Res<NetSession>>,
+) {
+    if session.is_some_and(|session| session.spectator) {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            Text::default(),
+            locale.text_font(18.),
+            TextColor(MENU_SELECTED_COLOR),
+            SpectatorText,
+            OnNetClientScreen,
+        ));
+    }
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnNetClientScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                locale.text_font(48.),
+                TextColor(MENU_SELECTED_COLOR),
+                TextLayout::new_with_justify(JustifyText::Center),
+                NetStateText,
+            ));
+        });
+}
+
+pub fn client_receive(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    mut view: ResMut<NetClientView>,
+    mut q_mirrored: Query<(
+        Entity,
+        &mut MirroredSprite,
+        &mut Sprite,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+    asset_server: Res<AssetServer>,
+    mut explosion_ew: EventWriter<ExplosionEvent>,
+    mut sound_ew: EventWriter<SoundEvent>,
+    mut lobby: ResMut<Lobby>,
+    time: Res<Time<Real>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut level_bounds: ResMut<LevelBounds>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let peer = session.peer;
+    for (address, message) in session.receive(now) {
+        if Some(address) != peer {
+            continue;
+        }
+        let snapshot = match message {
+            NetMessage::Snapshot(snapshot) => snapshot,
+            NetMessage::Bye => {
+                info!("Host left the game");
+                lobby.status = Some((TextKey::HostLeft, String::new()));
+                app_state.set(AppState::Lobby);
+                return;
+            }
+            _ => continue,
+        };
+        // 丢弃乱序到达的旧画面
+        if snapshot.frame < view.frame {
+            continue;
+        }
+        view.frame = snapshot.frame;
+        let [columns, rows] = snapshot.status.level_size;
+        let bounds = LevelBounds { columns, rows };
+        if columns > 0 && rows > 0 && *level_bounds != bounds {
+            *level_bounds = bounds;
+        }
+        view.status = snapshot.status;
+        for id in snapshot.removed {
+            if let Some(entity) = view.sprites.remove(&id) {
+                commands.entity(entity).despawn();
+            }
+        }
+        for net_sprite in snapshot.sprites {
+            let image = view
+                .images
+                .entry(net_sprite.image.clone())
+                .or_insert_with(|| asset_server.load(net_sprite.image.clone()))
+                .clone();
+            let [red, green, blue, alpha] = net_sprite.color;
+            let sprite = Sprite {
+                image,
+                rect: net_sprite
+                    .rect
+                    .map(|[x0, y0, x1, y1]| Rect::new(x0, y0, x1, y1)),
+                custom_size: net_sprite.size.map(Vec2::from_array),
+                color: Color::srgba(red, green, blue, alpha),
+                flip_x: net_sprite.flip[0],
+                flip_y: net_sprite.flip[1],
+                ..default()
+            };
+            let transform = Transform {
+                translation: Vec3::from_array(net_sprite.translation),
+                rotation: Quat::from_rotation_z(net_sprite.angle),
+                scale: Vec3::new(net_sprite.scale[0], net_sprite.scale[1], 1.),
+            };
+            let visibility = if net_sprite.visible {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            let mirrored = MirroredSprite {
+                id: net_sprite.id,
+                last_frame: view.frame,
+                player: net_sprite.player,
+            };
+            match view.sprites.get(&net_sprite.id) {
+                Some(&entity) => {
+                    if let Ok((
+                        _,
+                        mut old_mirrored,
+                        mut old_sprite,
+                        mut old_transform,
+                        mut old_visibility,
+                    )) = q_mirrored.get_mut(entity)
+                    {
+                        *old_mirrored = mirrored;
+                        *old_sprite = sprite;
+                        *old_transform = transform;
+                        *old_visibility = visibility;
+                    } else {
+                        // 同一帧内刚创建的精灵
+                        commands
+                            .entity(entity)
+                            .insert((mirrored, sprite, transform, visibility));
+                    }
+                }
+                None => {
+                    let entity = commands
+                        .spawn((mirrored, sprite, transform, visibility))
+                        .id();
+                    view.sprites.insert(net_sprite.id, entity);
+                }
+            }
+        }
+        for explosion in snapshot.explosions {
+            explosion_ew.send(ExplosionEvent {
+                pos: Vec3::from_array(explosion.pos),
+                explosion_type: if explosion.big {
+                    ExplosionType::BigExplosion
+                } else {
+                    ExplosionType::BulletExplosion
+                },
+            });
+        }
+        for sound in snapshot.sounds {
+            sound_ew.send(SoundEvent(sound));
+        }
+    }
+    // 删除消息丢失时，超过两个完整画面没有更新的精灵视为已删除
+    for (entity, mirrored, ..) in &q_mirrored {
+        if mirrored.last_frame + 2 * KEYFRAME_INTERVAL < view.frame {
+            view.sprites.remove(&mirrored.id);
+            commands.entity(entity).despawn();
+        }
+    }
+    if session.timed_out(now) {
+        warn!("Connection to host lost");
+        lobby.status = Some((TextKey::ConnectionLost, String::new()));
+        app_state.set(AppState::Lobby);
+    }
+}
+
+// 客户端的玩家可以使用任意一组按键，观众从不发送操作，只定时告知主机仍在观看
+pub fn client_send_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    session: Option<ResMut<NetSession>>,
+    time: Res<Time<Real>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        info!("Leave LAN game");
+        app_state.set(AppState::Lobby);
+        return;
+    }
+    if session.spectator {
+        let now = time.elapsed_secs();
+        if now - session.last_hello >= HELLO_INTERVAL_SECS {
+            session.last_hello = now;
+            session.send(&NetMessage::Hello {
+                version: PROTOCOL_VERSION,
+                spectator: true,
+            });
+        }
+        return;
+    }
+    let player1 = PlayerInput::from_keyboard(&keyboard_input, &PLAYER1_KEYS);
+    let player2 = PlayerInput::from_keyboard(&keyboard_input, &PLAYER2_KEYS);
+    session.send(&NetMessage::Input(PlayerInput {
+        direction: player1.direction.or(player2.direction),
+        fire: player1.fire || player2.fire,
+    }));
+}
+
+// 客户端镜头跟随双方玩家，观众可以按Tab在全景和跟随某个玩家之间切换
+pub fn net_client_camera(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    session: Option<Res<NetSession>>,
+    mut view: ResMut<NetClientView>,
+    level_bounds: Res<LevelBounds>,
+    q_mirrored: Query<(&MirroredSprite, &Transform), Without<GameCamera>>,
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    let Some(session) = session else {
+        return;
+    };
+    if session.spectator && keyboard_input.just_pressed(KeyCode::Tab) {
+        view.follow = match view.follow {
+            None => Some(1),
+            Some(1) => Some(2),
+            _ => None,
+        };
+    }
+    let players: Vec<(u32, Vec2)> = q_mirrored
+        .iter()
+        .filter_map(|(mirrored, transform)| {
+            mirrored
+                .player
+                .map(|player| (player, transform.translation.truncate()))
+        })
+        .collect();
+    let camera = match (session.spectator, view.follow) {
+        (false, _) => {
+            let targets: Vec<Vec2> = players.iter().map(|(_, pos)| *pos).collect();
+            camera_view(&level_bounds, VIEW_SIZE, &targets, 1.)
+        }
+        (true, None) => Some(level_overview(&level_bounds)),
+        // 跟随的玩家正在重生时镜头停在原地
+        (true, Some(follow)) => {
+            let targets: Vec<Vec2> = players
+                .iter()
+                .filter(|(player, _)| *player == follow)
+                .map(|(_, pos)| *pos)
+                .collect();
+            camera_view(&level_bounds, VIEW_SIZE, &targets, FOLLOW_CAMERA_SCALE)
+        }
+    };
+    let Some((center, scale)) = camera else {
+        return;
+    };
+    for (mut transform, mut projection) in &mut q_camera {
+        transform.translation = center.extend(transform.translation.z);
+        projection.scale = scale;
+    }
+}
+
+pub fn update_net_client_screen(
+    view: Res<NetClientView>,
+    locale: Res<Locale>,
+    mut q_hud_text: Query<
+        &mut Text,
+        (With<HudText>, Without<NetStateText>, Without<SpectatorText>),
+    >,
+    mut q_state_text: Query<&mut Text, (With<NetStateText>, Without<SpectatorText>)>,
+    mut q_spectator_text: Query<&mut Text, With<SpectatorText>>,
+) {
+    let status = &view.status;
+    let text = hud_text(
+        &locale,
+        stage_hud_text(&locale, status.stage, status.enemies),
+        &[
+            ("1P", status.lives[0], status.scores[0]),
+            ("2P", status.lives[1], status.scores[1]),
+        ],
+    );
+    for mut hud in &mut q_hud_text {
+        if hud.0 != text {
+            hud.0.clone_from(&text);
+        }
+    }
+    let state = match status.state {
+        NetGameState::Playing => "",
+        NetGameState::Paused => locale.tr(TextKey::Paused),
+        NetGameState::StageClear => locale.tr(TextKey::StageClear),
+        NetGameState::GameOver => locale.tr(TextKey::GameOver),
+    };
+    for mut text in &mut q_state_text {
+        if text.0 != state {
+            text.0 = state.to_string();
+        }
+    }
+    let view_name = match view.follow {
+        None => locale.tr(TextKey::AllView),
+        Some(1) => "1P",
+        Some(_) => "2P",
+    };
+    let spectating = format!(
+        "{}  [{}]  {}",
+        locale.tr(TextKey::Spectating),
+        view_name,
+        locale.tr(TextKey::SwitchViewHelp)
+    );
+    for mut text in &mut q_spectator_text {
+        if text.0 != spectating {
+            text.0.clone_from(&spectating);
+        }
+    }
+}
+
+pub fn cleanup_net_client(
+    mut commands: Commands,
+    mut view: ResMut<NetClientView>,
+    q_mirrored: Query<Entity, With<MirroredSprite>>,
+) {
+    for entity in &q_mirrored {
+        commands.entity(entity).despawn_recursive();
+    }
+    *view = NetClientView::default();
+}
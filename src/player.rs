@@ -1,10 +1,15 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::audio::{Sound, SoundEvent};
 use crate::bullet::*;
 use crate::common::{self, *};
+use crate::event_log::{GameEvent, PowerUpKind, Shooter};
 use crate::level::Player2Marker;
-use crate::level::{Player1Marker, LEVEL_TRANSLATION_OFFSET};
+use crate::level::{LevelBounds, Player1Marker};
+use crate::settings::Settings;
+use crate::sprites::SpriteAssets;
 
 // 出生保护盾
 #[derive(Component)]
@@ -24,34 +29,123 @@ pub struct BornRemoveTimer(pub Timer);
 #[reflect(Component)]
 pub struct PlayerNo(pub u32);
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, Copy, Event)]
 pub struct SpawnPlayerEvent {
     pos: Vec2,
     player_no: PlayerNo,
 }
 
+// 玩家按键：上、下、左、右、开火
+pub const PLAYER1_KEYS: [KeyCode; 5] = [
+    KeyCode::KeyW,
+    KeyCode::KeyS,
+    KeyCode::KeyA,
+    KeyCode::KeyD,
+    KeyCode::Space,
+];
+pub const PLAYER2_KEYS: [KeyCode; 5] = [
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Enter,
+];
+
+// 一帧的玩家操作，开火记录按键是否按下
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub direction: Option<common::Direction>,
+    pub fire: bool,
+}
+
+impl PlayerInput {
+    pub fn from_keyboard(keyboard_input: &ButtonInput<KeyCode>, keys: &[KeyCode; 5]) -> Self {
+        // 一次只能移动一个方向
+        let direction = if keyboard_input.pressed(keys[0]) {
+            Some(common::Direction::Up)
+        } else if keyboard_input.pressed(keys[1]) {
+            Some(common::Direction::Down)
+        } else if keyboard_input.pressed(keys[2]) {
+            Some(common::Direction::Left)
+        } else if keyboard_input.pressed(keys[3]) {
+            Some(common::Direction::Right)
+        } else {
+            None
+        };
+        PlayerInput {
+            direction,
+            fire: keyboard_input.pressed(keys[4]),
+        }
+    }
+}
+
+// 两个玩家当前帧和上一帧的操作
+#[derive(Debug, Resource, Default)]
+pub struct PlayerInputs {
+    pub current: [PlayerInput; 2],
+    pub previous: [PlayerInput; 2],
+}
+
+impl PlayerInputs {
+    pub fn get(&self, player_no: PlayerNo) -> PlayerInput {
+        self.current[player_no.0 as usize - 1]
+    }
+
+    // 开火键刚按下
+    pub fn fire_pressed(&self, player_no: PlayerNo) -> bool {
+        let i = player_no.0 as usize - 1;
+        self.current[i].fire && !self.previous[i].fire
+    }
+}
+
+// 存在时玩家2由联机的另一端操作
+#[derive(Debug, Resource)]
+pub struct RemotePlayer2;
+
 #[derive(Debug, Resource)]
 pub struct PlayerLives {
     pub player1: i8,
     pub player2: i8,
 }
 
+#[derive(Debug, Resource, Default)]
+pub struct PlayerScores {
+    pub player1: u32,
+    pub player2: u32,
+}
+
+impl PlayerScores {
+    pub fn add(&mut self, player_no: PlayerNo, score: u32) {
+        if player_no.0 == 1 {
+            self.player1 += score;
+        } else if player_no.0 == 2 {
+            self.player2 += score;
+        }
+    }
+}
+
 pub fn auto_spawn_players(
     mut commands: Commands,
     q_players: Query<&PlayerNo>,
     q_player1_marker: Query<&Transform, With<Player1Marker>>,
     q_player2_marker: Query<&Transform, With<Player2Marker>>,
+    q_born: Query<&PlayerNo, With<Born>>,
     mut spawn_player_er: EventReader<SpawnPlayerEvent>,
-    mut spawning_player1: Local<bool>,
-    mut spawning_player2: Local<bool>,
     multiplayer_mode: Res<MultiplayerMode>,
     mut player_lives: ResMut<PlayerLives>,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: Res<SpriteAssets>,
+    level_bounds: Res<LevelBounds>,
+    mut game_event_ew: EventWriter<GameEvent>,
 ) {
+    // 出生动画播放中或待创建的玩家视为正在出生，出生动画被清理后可以重新出生
+    let spawn_player_events: Vec<SpawnPlayerEvent> = spawn_player_er.read().copied().collect();
     let mut player1_exists = false;
     let mut player2_exists = false;
-    for player in &q_players {
+    for player in q_players
+        .iter()
+        .chain(q_born.iter())
+        .chain(spawn_player_events.iter().map(|event| &event.player_no))
+    {
         if player.0 == 1 {
             player1_exists = true;
         }
@@ -61,90 +155,51 @@ pub fn auto_spawn_players(
     }
     if !player1_exists {
         for player1_marker in &q_player1_marker {
-            if !*spawning_player1 && player_lives.player1 > 0 {
+            if !player1_exists && player_lives.player1 > 0 {
                 // 出生动画
                 spawn_born(
-                    player1_marker.translation + LEVEL_TRANSLATION_OFFSET,
+                    player1_marker.translation + level_bounds.offset(),
                     PlayerNo(1),
                     &mut commands,
-                    &asset_server,
-                    &mut atlas_layouts,
+                    &sprite_assets,
                 );
-                *spawning_player1 = true;
+                player1_exists = true;
             }
         }
     }
     if !player2_exists && *multiplayer_mode == MultiplayerMode::TwoPlayers {
         for player2_marker in &q_player2_marker {
-            if !*spawning_player2 && player_lives.player2 > 0 {
+            if !player2_exists && player_lives.player2 > 0 {
                 // 出生动画
                 spawn_born(
-                    player2_marker.translation + LEVEL_TRANSLATION_OFFSET,
+                    player2_marker.translation + level_bounds.offset(),
                     PlayerNo(2),
                     &mut commands,
-                    &asset_server,
-                    &mut atlas_layouts,
+                    &sprite_assets,
                 );
-                *spawning_player2 = true;
+                player2_exists = true;
             }
         }
     }
 
-    let shield_texture_handle = asset_server.load("textures/shield.bmp");
-    let shield_texture_atlas = TextureAtlasLayout::from_grid(UVec2::new(31, 31), 1, 2, None, None);
-    let shield_atlas_layout_handle = atlas_layouts.add(shield_texture_atlas);
-
-    // 玩家1
-    let player1_texture_handle = asset_server.load("textures/tank1.bmp");
-    let player1_texture_atlas =
-        TextureAtlasLayout::from_grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 4, None, None);
-    let player1_atlas_layout_handle = atlas_layouts.add(player1_texture_atlas);
-
-    // 玩家2
-    let player2_texture_handle = asset_server.load("textures/tank2.bmp");
-    let player2_texture_atlas =
-        TextureAtlasLayout::from_grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 4, None, None);
-    let player2_atlas_layout_handle = atlas_layouts.add(player2_texture_atlas);
-
     // 出生动画完毕后，进行player创建
-    for spawn_player_event in spawn_player_er.read() {
-        dbg!(spawn_player_event);
+    for spawn_player_event in &spawn_player_events {
         // 保护盾
-        let shield = commands
-            .spawn((
-                Shield,
-                Sprite {
-                    image: shield_texture_handle.clone(),
-                    texture_atlas: Some(TextureAtlas {
-                        index: 0,
-                        layout: shield_atlas_layout_handle.clone(),
-                    }),
-                    ..default()
-                },
-                Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)), // 通过z轴控制sprite order
-                AnimationTimer(Timer::from_seconds(0.2, TimerMode::Repeating)),
-                AnimationIndices { first: 0, last: 1 },
-                ShieldRemoveTimer(Timer::from_seconds(5.0, TimerMode::Once)),
-            ))
-            .id();
+        let shield = spawn_shield(&mut commands, &sprite_assets);
+        game_event_ew.send(GameEvent::PowerUp {
+            player: spawn_player_event.player_no.0,
+            kind: PowerUpKind::SpawnShield,
+        });
 
         // 坦克
         let tank = commands
             .spawn((
                 spawn_player_event.player_no,
                 Sprite {
-                    image: if spawn_player_event.player_no.0 == 1 {
-                        player1_texture_handle.clone()
-                    } else {
-                        player2_texture_handle.clone()
-                    },
+                    image: sprite_assets.player(spawn_player_event.player_no.0),
                     texture_atlas: Some(TextureAtlas {
                         index: 0,
-                        layout: if spawn_player_event.player_no.0 == 1 {
-                            player1_atlas_layout_handle.clone()
-                        } else {
-                            player2_atlas_layout_handle.clone()
-                        },
+                        layout: sprite_assets.tank_layout.clone(),
                     }),
                     ..default()
                 },
@@ -177,37 +232,45 @@ pub fn auto_spawn_players(
         } else if spawn_player_event.player_no.0 == 2 {
             player_lives.player2 -= 1;
         }
-
-        // 重置状态
-        if spawn_player_event.player_no.0 == 1 {
-            *spawning_player1 = false;
-        } else if spawn_player_event.player_no.0 == 2 {
-            *spawning_player2 = false;
-        }
     }
 }
 
+// 保护盾，需要作为坦克的子实体
+pub fn spawn_shield(commands: &mut Commands, sprite_assets: &SpriteAssets) -> Entity {
+    commands
+        .spawn((
+            Shield,
+            Sprite {
+                image: sprite_assets.shield.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    index: 0,
+                    layout: sprite_assets.shield_layout.clone(),
+                }),
+                ..default()
+            },
+            Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)), // 通过z轴控制sprite order
+            AnimationTimer(Timer::from_seconds(0.2, TimerMode::Repeating)),
+            AnimationIndices { first: 0, last: 1 },
+            ShieldRemoveTimer(Timer::from_seconds(5.0, TimerMode::Once)),
+        ))
+        .id()
+}
+
 pub fn spawn_born(
     pos: Vec3,
     player_no: PlayerNo,
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: &SpriteAssets,
 ) {
     // 出生特效
-    println!("spawn born once");
-    let born_texture_handle = asset_server.load("textures/born.bmp");
-
-    let born_texture_atlas = TextureAtlasLayout::from_grid(UVec2::new(32, 32), 4, 1, None, None);
-    let born_atlas_layout_handle = atlas_layouts.add(born_texture_atlas);
     commands.spawn((
         Born,
         player_no,
         Sprite {
-            image: born_texture_handle,
+            image: sprite_assets.born.clone(),
             texture_atlas: Some(TextureAtlas {
                 index: 0,
-                layout: born_atlas_layout_handle,
+                layout: sprite_assets.born_layout.clone(),
             }),
             ..default()
         },
@@ -218,9 +281,22 @@ pub fn spawn_born(
     ));
 }
 
+// 读取本地键盘操作，联机主机的玩家2操作由网络接收
+pub fn read_player_inputs(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut player_inputs: ResMut<PlayerInputs>,
+    remote_player2: Option<Res<RemotePlayer2>>,
+) {
+    player_inputs.previous = player_inputs.current;
+    player_inputs.current[0] = PlayerInput::from_keyboard(&keyboard_input, &PLAYER1_KEYS);
+    if remote_player2.is_none() {
+        player_inputs.current[1] = PlayerInput::from_keyboard(&keyboard_input, &PLAYER2_KEYS);
+    }
+}
+
 // 玩家移动坦克
 pub fn players_move(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    player_inputs: Res<PlayerInputs>,
     mut query: Query<(
         &PlayerNo,
         &mut Velocity,
@@ -230,52 +306,17 @@ pub fn players_move(
     )>,
 ) {
     for (player_no, mut velocity, mut direction, mut sprite, mut indices) in &mut query {
-        if player_no.0 == 1
-            && keyboard_input.any_just_released([
-                KeyCode::KeyW,
-                KeyCode::KeyS,
-                KeyCode::KeyA,
-                KeyCode::KeyD,
-            ])
-        {
-            velocity.linvel = Vec2::ZERO;
-            continue;
-        }
-        if player_no.0 == 2
-            && keyboard_input.any_just_released([
-                KeyCode::ArrowUp,
-                KeyCode::ArrowDown,
-                KeyCode::ArrowLeft,
-                KeyCode::ArrowRight,
-            ])
-        {
+        let Some(input_direction) = player_inputs.get(*player_no).direction else {
             velocity.linvel = Vec2::ZERO;
             continue;
-        }
-        // 一次只能移动一个方向
-        if (player_no.0 == 1 && keyboard_input.pressed(KeyCode::KeyW))
-            || (player_no.0 == 2 && keyboard_input.pressed(KeyCode::ArrowUp))
-        {
-            velocity.linvel = Vec2::new(0.0, PLAYER_SPEED);
-            *direction = common::Direction::Up;
-        } else if (player_no.0 == 1 && keyboard_input.pressed(KeyCode::KeyS))
-            || (player_no.0 == 2 && keyboard_input.pressed(KeyCode::ArrowDown))
-        {
-            velocity.linvel = Vec2::new(0.0, -PLAYER_SPEED);
-            *direction = common::Direction::Down;
-        } else if (player_no.0 == 1 && keyboard_input.pressed(KeyCode::KeyA))
-            || (player_no.0 == 2 && keyboard_input.pressed(KeyCode::ArrowLeft))
-        {
-            velocity.linvel = Vec2::new(-PLAYER_SPEED, 0.0);
-            *direction = common::Direction::Left;
-        } else if (player_no.0 == 1 && keyboard_input.pressed(KeyCode::KeyD))
-            || (player_no.0 == 2 && keyboard_input.pressed(KeyCode::ArrowRight))
-        {
-            velocity.linvel = Vec2::new(PLAYER_SPEED, 0.0);
-            *direction = common::Direction::Right;
-        } else {
-            continue;
-        }
+        };
+        *direction = input_direction;
+        velocity.linvel = match input_direction {
+            common::Direction::Up => Vec2::new(0.0, PLAYER_SPEED),
+            common::Direction::Down => Vec2::new(0.0, -PLAYER_SPEED),
+            common::Direction::Left => Vec2::new(-PLAYER_SPEED, 0.0),
+            common::Direction::Right => Vec2::new(PLAYER_SPEED, 0.0),
+        };
 
         match *direction {
             common::Direction::Up => {
@@ -324,7 +365,7 @@ pub fn animate_players(
 // 玩家攻击
 pub fn players_attack(
     mut commands: Commands,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    player_inputs: Res<PlayerInputs>,
     mut q_players: Query<(
         &PlayerNo,
         &Transform,
@@ -332,30 +373,29 @@ pub fn players_attack(
         &mut TankRefreshBulletTimer,
     )>,
     time: Res<Time>,
-    game_sounds: Res<GameSounds>,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+    mut game_event_ew: EventWriter<GameEvent>,
+    sprite_assets: Res<SpriteAssets>,
 ) {
     for (player_no, transform, direction, mut refresh_bullet_timer) in &mut q_players {
         refresh_bullet_timer.tick(time.delta());
-        if (player_no.0 == 1 && keyboard_input.just_pressed(KeyCode::Space))
-            || (player_no.0 == 2 && keyboard_input.just_pressed(KeyCode::Enter))
-        {
-            if refresh_bullet_timer.finished() {
-                spawn_bullet(
-                    &mut commands,
-                    &asset_server,
-                    &mut atlas_layouts,
-                    Bullet::Player,
-                    transform.translation,
-                    direction.clone(),
-                );
-                commands.spawn((
-                    AudioPlayer(game_sounds.player_fire.clone()),
-                    PlaybackSettings::DESPAWN,
-                ));
-                refresh_bullet_timer.reset();
-            }
+        if player_inputs.fire_pressed(*player_no) && refresh_bullet_timer.finished() {
+            let bullet = spawn_bullet(
+                &mut commands,
+                &sprite_assets,
+                Bullet::Player,
+                transform.translation,
+                *direction,
+            );
+            commands.entity(bullet).insert(BulletOwner(*player_no));
+            sound_ew.send(SoundEvent(Sound::PlayerFire));
+            game_event_ew.send(GameEvent::ShotFired {
+                shooter: Shooter::player(*player_no),
+                x: transform.translation.x,
+                y: transform.translation.y,
+                direction: *direction,
+            });
+            refresh_bullet_timer.reset();
         }
     }
 }
@@ -432,7 +472,7 @@ pub fn animate_born(
             commands.entity(entity).despawn();
             spawn_player_ew.send(SpawnPlayerEvent {
                 pos: transform.translation.truncate(),
-                player_no: player_no.clone(),
+                player_no: *player_no,
             });
         }
     }
@@ -450,7 +490,11 @@ pub fn cleanup_born(mut commands: Commands, q_born: Query<Entity, With<Born>>) {
     }
 }
 
-pub fn reset_player_lives(mut player_lives: ResMut<PlayerLives>) {
-    player_lives.player1 = 3;
-    player_lives.player2 = 3;
+pub fn reset_player_lives(mut player_lives: ResMut<PlayerLives>, settings: Res<Settings>) {
+    player_lives.player1 = settings.starting_lives;
+    player_lives.player2 = settings.starting_lives;
+}
+
+pub fn reset_player_scores(mut player_scores: ResMut<PlayerScores>) {
+    *player_scores = PlayerScores::default();
 }
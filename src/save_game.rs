@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{AppState, MultiplayerMode};
+use crate::enemy::{Enemy, LevelSpawnedEnemies};
+use crate::generator::GeneratedLevel;
+use crate::map::{CustomLevel, LevelMap, LevelPack};
+use crate::player::{PlayerLives, PlayerNo, PlayerScores};
+use crate::storage;
+use crate::ui::{StartMenuCursor, StartMenuItem};
+
+pub const SAVE_GAME_FILE: &str = "save_game.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub lives: i8,
+    pub score: u32,
+}
+
+// 存档只保存关卡进度，读档后关卡从头开始，存活的敌人会重新生成
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub level: usize,
+    pub custom_level: Option<String>,
+    pub generated_seed: Option<u64>,
+    pub level_pack_index: usize,
+    pub multiplayer_mode: MultiplayerMode,
+    pub spawned_enemies: i32,
+    pub player1: PlayerSave,
+    pub player2: PlayerSave,
+}
+
+// 唯一的存档位
+#[derive(Debug, Resource, Default)]
+pub struct SaveSlot(pub Option<SaveGame>);
+
+impl SaveSlot {
+    pub fn load() -> Self {
+        let save_game = storage::load_text(SAVE_GAME_FILE).and_then(|text| {
+            serde_json::from_str(&text)
+                .map_err(|e| warn!("Failed to parse {}: {}", SAVE_GAME_FILE, e))
+                .ok()
+        });
+        SaveSlot(save_game)
+    }
+
+    pub fn save(&mut self, save_game: SaveGame) {
+        let result = serde_json::to_string_pretty(&save_game)
+            .map_err(|e| e.to_string())
+            .and_then(|text| storage::save_text(SAVE_GAME_FILE, &text));
+        if let Err(e) = result {
+            warn!("Failed to save {}: {}", SAVE_GAME_FILE, e);
+        }
+        self.0 = Some(save_game);
+    }
+
+    pub fn take(&mut self) -> Option<SaveGame> {
+        storage::remove(SAVE_GAME_FILE);
+        self.0.take()
+    }
+}
+
+// 从暂停菜单退出到开始菜单时存档
+pub fn save_on_quit(
+    mut save_slot: ResMut<SaveSlot>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    generated_level: Res<GeneratedLevel>,
+    level_pack: Res<LevelPack>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    level_spawned_enemies: Res<LevelSpawnedEnemies>,
+    q_enemies: Query<(), With<Enemy>>,
+    q_players: Query<&PlayerNo>,
+    player_lives: Res<PlayerLives>,
+    player_scores: Res<PlayerScores>,
+) {
+    let level = match *level_selection {
+        LevelSelection::Indices(LevelIndices { level, .. }) => level,
+        _ => 0,
+    };
+    // 场上玩家出生时已扣除生命，读档后会重新出生
+    let alive = |player_no: u32| q_players.iter().any(|player| player.0 == player_no) as i8;
+    let save_game = SaveGame {
+        level,
+        custom_level: custom_level.0.as_ref().map(LevelMap::to_text),
+        generated_seed: generated_level.0,
+        level_pack_index: level_pack.current,
+        multiplayer_mode: *multiplayer_mode,
+        spawned_enemies: level_spawned_enemies.0 - q_enemies.iter().len() as i32,
+        player1: PlayerSave {
+            lives: player_lives.player1 + alive(1),
+            score: player_scores.player1,
+        },
+        player2: PlayerSave {
+            lives: player_lives.player2 + alive(2),
+            score: player_scores.player2,
+        },
+    };
+    info!("Save game: {:?}", save_game);
+    save_slot.save(save_game);
+}
+
+// 开始菜单选择CONTINUE时读取存档，读取后删除存档
+pub fn continue_saved_game(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    start_menu_cursor: Res<StartMenuCursor>,
+    mut save_slot: ResMut<SaveSlot>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut custom_level: ResMut<CustomLevel>,
+    mut generated_level: ResMut<GeneratedLevel>,
+    mut level_pack: ResMut<LevelPack>,
+    mut multiplayer_mode: ResMut<MultiplayerMode>,
+    mut level_spawned_enemies: ResMut<LevelSpawnedEnemies>,
+    mut player_lives: ResMut<PlayerLives>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if start_menu_cursor.item() != StartMenuItem::Continue
+        || !keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+    {
+        return;
+    }
+    let Some(save_game) = save_slot.take() else {
+        return;
+    };
+    custom_level.0 = match save_game.custom_level.as_deref().map(LevelMap::from_text) {
+        Some(Ok(map)) => Some(map),
+        Some(Err(e)) => {
+            warn!("Broken custom level in save game: {}", e);
+            return;
+        }
+        None => None,
+    };
+    info!("Continue saved game: {:?}", save_game);
+    *level_selection = LevelSelection::index(save_game.level);
+    generated_level.0 = save_game.generated_seed;
+    level_pack.current = save_game.level_pack_index;
+    *multiplayer_mode = save_game.multiplayer_mode;
+    level_spawned_enemies.0 = save_game.spawned_enemies;
+    player_lives.player1 = save_game.player1.lives;
+    player_lives.player2 = save_game.player2.lives;
+    player_scores.player1 = save_game.player1.score;
+    player_scores.player2 = save_game.player2.score;
+    app_state.set(AppState::Playing);
+}
@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+use bevy::window::{MonitorSelection, PresentMode, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{Sound, SoundChannel, SoundEvent};
+use crate::camera::{camera_viewport, SplitCamera, SplitScreen, UiCamera};
+use crate::common::{AppState, VIEW_HEIGHT, VIEW_WIDTH};
+use crate::locale::{Language, Locale, LocalizedText, TextKey};
+use crate::storage;
+use crate::ui::MENU_SELECTED_COLOR;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+const VOLUME_STEP: f32 = 0.1;
+const MAX_STARTING_LIVES: i8 = 9;
+
+// 游戏设置，修改后立即生效，离开选项菜单时保存
+#[derive(Debug, Clone, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub ui_volume: f32,
+    pub fullscreen: bool,
+    pub integer_scaling: bool,
+    pub vsync: bool,
+    pub starting_lives: i8,
+    pub language: Language,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            ui_volume: 1.0,
+            fullscreen: false,
+            integer_scaling: false,
+            vsync: true,
+            starting_lives: 3,
+            language: Language::English,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let Some(text) = storage::load_text(SETTINGS_FILE) else {
+            return Settings::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            warn!("Failed to parse {}: {}", SETTINGS_FILE, e);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| storage::save_text(SETTINGS_FILE, &text));
+        if let Err(e) = result {
+            warn!("Failed to save {}: {}", SETTINGS_FILE, e);
+        }
+    }
+
+    pub fn volume(&self, channel: SoundChannel) -> f32 {
+        self.master_volume
+            * match channel {
+                SoundChannel::Music => self.music_volume,
+                SoundChannel::Sfx => self.sfx_volume,
+                SoundChannel::Ui => self.ui_volume,
+            }
+    }
+}
+
+#[derive(Component)]
+pub struct OnOptionsScreen;
+
+#[derive(Component)]
+pub struct OptionsText(pub OptionsItem);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionsItem {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    UiVolume,
+    Fullscreen,
+    IntegerScaling,
+    Vsync,
+    StartingLives,
+    Language,
+    Back,
+}
+
+impl OptionsItem {
+    pub const ALL: [OptionsItem; 10] = [
+        OptionsItem::MasterVolume,
+        OptionsItem::MusicVolume,
+        OptionsItem::SfxVolume,
+        OptionsItem::UiVolume,
+        OptionsItem::Fullscreen,
+        OptionsItem::IntegerScaling,
+        OptionsItem::Vsync,
+        OptionsItem::StartingLives,
+        OptionsItem::Language,
+        OptionsItem::Back,
+    ];
+
+    pub fn text(self, settings: &Settings, locale: &Locale) -> String {
+        let on_off = |on: bool| locale.tr(if on { TextKey::On } else { TextKey::Off });
+        let percent = |volume: f32| format!("{:.0}%", volume * 100.);
+        let (label, value) = match self {
+            OptionsItem::MasterVolume => (TextKey::MasterVolume, percent(settings.master_volume)),
+            OptionsItem::MusicVolume => (TextKey::MusicVolume, percent(settings.music_volume)),
+            OptionsItem::SfxVolume => (TextKey::SfxVolume, percent(settings.sfx_volume)),
+            OptionsItem::UiVolume => (TextKey::UiVolume, percent(settings.ui_volume)),
+            OptionsItem::Fullscreen => (
+                TextKey::Display,
+                locale
+                    .tr(if settings.fullscreen {
+                        TextKey::Fullscreen
+                    } else {
+                        TextKey::Windowed
+                    })
+                    .to_string(),
+            ),
+            OptionsItem::IntegerScaling => (
+                TextKey::IntegerScaling,
+                on_off(settings.integer_scaling).into(),
+            ),
+            OptionsItem::Vsync => (TextKey::Vsync, on_off(settings.vsync).into()),
+            OptionsItem::StartingLives => {
+                (TextKey::StartingLives, settings.starting_lives.to_string())
+            }
+            OptionsItem::Language => (TextKey::Language, settings.language.name().to_string()),
+            OptionsItem::Back => return locale.tr(TextKey::Back).to_string(),
+        };
+        format!("{:<16} < {:^10} >", locale.tr(label), value)
+    }
+
+    // 左右键修改设置，delta为1或-1
+    pub fn change(self, settings: &mut Settings, locale: &Locale, delta: i8) {
+        let step_volume = |volume: &mut f32| {
+            *volume = (*volume + delta as f32 * VOLUME_STEP).clamp(0., 1.);
+            // 避免浮点累计误差
+            *volume = (*volume * 10.).round() / 10.;
+        };
+        match self {
+            OptionsItem::MasterVolume => step_volume(&mut settings.master_volume),
+            OptionsItem::MusicVolume => step_volume(&mut settings.music_volume),
+            OptionsItem::SfxVolume => step_volume(&mut settings.sfx_volume),
+            OptionsItem::UiVolume => step_volume(&mut settings.ui_volume),
+            OptionsItem::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            OptionsItem::IntegerScaling => settings.integer_scaling = !settings.integer_scaling,
+            OptionsItem::Vsync => settings.vsync = !settings.vsync,
+            OptionsItem::Language => {
+                // 跳过缺少字体的语言
+                let next = settings.language.next();
+                if locale.is_available(next) {
+                    settings.language = next;
+                }
+            }
+            OptionsItem::StartingLives => {
+                settings.starting_lives =
+                    (settings.starting_lives + delta).clamp(1, MAX_STARTING_LIVES);
+            }
+            OptionsItem::Back => {}
+        }
+    }
+}
+
+// 选项菜单光标和退出后返回的状态
+#[derive(Debug, Resource)]
+pub struct OptionsMenu {
+    pub cursor: usize,
+    pub return_state: AppState,
+}
+
+impl Default for OptionsMenu {
+    fn default() -> Self {
+        OptionsMenu {
+            cursor: 0,
+            return_state: AppState::StartMenu,
+        }
+    }
+}
+
+pub fn setup_options_menu(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    settings: Res<Settings>,
+    mut options_menu: ResMut<OptionsMenu>,
+) {
+    options_menu.cursor = 0;
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+            OnOptionsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::Options)),
+                locale.text_font(36.),
+                LocalizedText(TextKey::Options),
+                Node {
+                    margin: UiRect::bottom(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+            for item in OptionsItem::ALL {
+                parent.spawn((
+                    Text::new(item.text(&settings, &locale)),
+                    locale.text_font(22.),
+                    TextColor(Color::WHITE),
+                    OptionsText(item),
+                ));
+            }
+        });
+}
+
+pub fn options_menu_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut options_menu: ResMut<OptionsMenu>,
+    mut settings: ResMut<Settings>,
+    locale: Res<Locale>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+) {
+    let items = OptionsItem::ALL.len();
+    let item = OptionsItem::ALL[options_menu.cursor];
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        options_menu.cursor = (options_menu.cursor + 1) % items;
+    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        options_menu.cursor = (options_menu.cursor + items - 1) % items;
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        item.change(&mut settings, &locale, 1);
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        item.change(&mut settings, &locale, -1);
+    } else if keyboard_input.just_pressed(KeyCode::Escape)
+        || (item == OptionsItem::Back
+            && keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]))
+    {
+        app_state.set(options_menu.return_state.clone());
+        return;
+    } else if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        item.change(&mut settings, &locale, 1);
+    } else {
+        return;
+    }
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
+
+pub fn update_options_screen(
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    options_menu: Res<OptionsMenu>,
+    mut q_options: Query<(&OptionsText, &mut Text, &mut TextColor)>,
+) {
+    for (options_text, mut text, mut text_color) in &mut q_options {
+        text.0 = options_text.0.text(&settings, &locale);
+        text_color.0 = if OptionsItem::ALL[options_menu.cursor] == options_text.0 {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+pub fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+pub fn apply_window_settings(settings: Res<Settings>, mut q_windows: Query<&mut Window>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut window in &mut q_windows {
+        window.mode = if settings.fullscreen {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+        } else {
+            WindowMode::Windowed
+        };
+        window.present_mode = if settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+}
+
+// 画面按设计分辨率等比缩放到窗口中央，两侧留黑边，UI跟随一起缩放
+// 整数倍缩放时只使用窗口能容纳的最大整数倍
+pub fn apply_camera_scaling(
+    settings: Res<Settings>,
+    q_windows: Query<&Window>,
+    mut q_cameras: Query<(&mut Camera, Has<UiCamera>, Has<SplitCamera>)>,
+    mut ui_scale: ResMut<UiScale>,
+    split_screen: Res<SplitScreen>,
+) {
+    let Ok(window) = q_windows.get_single() else {
+        return;
+    };
+    let window_size = window.physical_size();
+    if window_size.x == 0 || window_size.y == 0 {
+        // 窗口最小化
+        return;
+    }
+    let mut factor = (window_size.x as f32 / VIEW_WIDTH).min(window_size.y as f32 / VIEW_HEIGHT);
+    if settings.integer_scaling && factor >= 1. {
+        factor = factor.floor();
+    }
+    let size = (Vec2::new(VIEW_WIDTH, VIEW_HEIGHT) * factor)
+        .round()
+        .as_uvec2()
+        .min(window_size);
+    let position = (window_size - size) / 2;
+    for (mut camera, ui_camera, split_camera) in &mut q_cameras {
+        let viewport = camera_viewport(position, size, split_screen.0, ui_camera, split_camera);
+        let unchanged = camera.viewport.as_ref().is_some_and(|current| {
+            current.physical_position == viewport.physical_position
+                && current.physical_size == viewport.physical_size
+        });
+        if !unchanged {
+            camera.viewport = Some(viewport);
+        }
+    }
+    let scale = factor / window.scale_factor();
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+}
@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+
+use crate::common::TANK_SIZE;
+
+// 启动时加载的贴图和图集布局，生成实体时共用，避免每次生成都新建布局资源
+#[derive(Debug, Resource, Clone)]
+pub struct SpriteAssets {
+    pub map: Handle<Image>,
+    pub map_layout: Handle<TextureAtlasLayout>,
+    pub player1: Handle<Image>,
+    pub player2: Handle<Image>,
+    pub tank_layout: Handle<TextureAtlasLayout>,
+    pub enemies: Handle<Image>,
+    pub enemies_layout: Handle<TextureAtlasLayout>,
+    pub shield: Handle<Image>,
+    pub shield_layout: Handle<TextureAtlasLayout>,
+    pub born: Handle<Image>,
+    pub born_layout: Handle<TextureAtlasLayout>,
+    pub bullet: Handle<Image>,
+    pub bullet_layout: Handle<TextureAtlasLayout>,
+}
+
+impl SpriteAssets {
+    pub fn player(&self, player_no: u32) -> Handle<Image> {
+        if player_no == 2 {
+            self.player2.clone()
+        } else {
+            self.player1.clone()
+        }
+    }
+}
+
+// 初始状态的界面在Startup之前创建，需要在构建App时插入
+impl FromWorld for SpriteAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>().clone();
+        let mut atlas_layouts = world.resource_mut::<Assets<TextureAtlasLayout>>();
+        let mut grid = |tile_size: UVec2, columns: u32, rows: u32| {
+            atlas_layouts.add(TextureAtlasLayout::from_grid(
+                tile_size, columns, rows, None, None,
+            ))
+        };
+        SpriteAssets {
+            map: asset_server.load("textures/map.bmp"),
+            map_layout: grid(UVec2::new(32, 32), 7, 1),
+            player1: asset_server.load("textures/tank1.bmp"),
+            player2: asset_server.load("textures/tank2.bmp"),
+            tank_layout: grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 4),
+            enemies: asset_server.load("textures/enemies.bmp"),
+            enemies_layout: grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 8),
+            shield: asset_server.load("textures/shield.bmp"),
+            shield_layout: grid(UVec2::new(31, 31), 1, 2),
+            born: asset_server.load("textures/born.bmp"),
+            born_layout: grid(UVec2::new(32, 32), 4, 1),
+            bullet: asset_server.load("textures/bullet.bmp"),
+            bullet_layout: grid(UVec2::new(7, 8), 4, 1),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy_ecs_ldtk::ldtk::raw_level_accessor::RawLevelAccessor;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::audio::{Sound, SoundEvent};
+use crate::common::{AppState, LEVEL_COLUMNS, LEVEL_ROWS, MAX_LEVELS};
+use crate::level::LevelsProject;
+use crate::locale::{Locale, TextKey};
+use crate::map::{CustomLevel, LevelMap, MapTile};
+use crate::storage;
+use crate::ui::MENU_SELECTED_COLOR;
+
+pub const UNLOCKED_STAGES_FILE: &str = "unlocked_stages.txt";
+
+// 缩略图中每个格子的像素数
+const THUMBNAIL_TILE_PIXELS: u32 = 6;
+const STAGE_LOCKED_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+
+#[derive(Component)]
+pub struct OnStageSelectScreen;
+
+// 关卡卡片，包含缩略图和关卡名
+#[derive(Component)]
+pub struct StageSelectItem(pub usize);
+
+#[derive(Component)]
+pub struct StageThumbnail(pub usize);
+
+// 已解锁的关卡数量，到达新关卡时解锁
+#[derive(Debug, Resource)]
+pub struct UnlockedStages(pub usize);
+
+impl Default for UnlockedStages {
+    fn default() -> Self {
+        UnlockedStages(1)
+    }
+}
+
+impl UnlockedStages {
+    pub fn load() -> Self {
+        let Some(text) = storage::load_text(UNLOCKED_STAGES_FILE) else {
+            return UnlockedStages::default();
+        };
+        match text.trim().parse::<usize>() {
+            Ok(count) => UnlockedStages(count.clamp(1, MAX_LEVELS as usize)),
+            Err(e) => {
+                warn!("Failed to parse {}: {}", UNLOCKED_STAGES_FILE, e);
+                UnlockedStages::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = storage::save_text(UNLOCKED_STAGES_FILE, &self.0.to_string()) {
+            warn!("Failed to save {}: {}", UNLOCKED_STAGES_FILE, e);
+        }
+    }
+}
+
+#[derive(Debug, Resource, Default)]
+pub struct StageSelectCursor(pub usize);
+
+pub fn setup_stage_select(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    unlocked_stages: Res<UnlockedStages>,
+    mut stage_select_cursor: ResMut<StageSelectCursor>,
+) {
+    stage_select_cursor.0 = stage_select_cursor.0.min(unlocked_stages.0 - 1);
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(30.),
+                ..default()
+            },
+            OnStageSelectScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::SelectStage)),
+                locale.text_font(32.),
+            ));
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(30.),
+                    row_gap: Val::Px(20.),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for stage in 0..MAX_LEVELS as usize {
+                        parent
+                            .spawn((
+                                Node {
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    padding: UiRect::all(Val::Px(6.)),
+                                    border: UiRect::all(Val::Px(3.)),
+                                    row_gap: Val::Px(6.),
+                                    ..default()
+                                },
+                                BorderColor(Color::NONE),
+                                StageSelectItem(stage),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Node {
+                                        width: Val::Px(
+                                            (LEVEL_COLUMNS as u32 * THUMBNAIL_TILE_PIXELS) as f32,
+                                        ),
+                                        height: Val::Px(
+                                            (LEVEL_ROWS as u32 * THUMBNAIL_TILE_PIXELS) as f32,
+                                        ),
+                                        ..default()
+                                    },
+                                    BackgroundColor(Color::srgb(0.1, 0.1, 0.1)),
+                                    StageThumbnail(stage),
+                                ));
+                                parent.spawn((
+                                    Text::new(if stage < unlocked_stages.0 {
+                                        format!("{} {}", locale.tr(TextKey::Stage), stage + 1)
+                                    } else {
+                                        locale.tr(TextKey::Locked).to_string()
+                                    }),
+                                    locale.text_font(18.),
+                                    TextColor(if stage < unlocked_stages.0 {
+                                        Color::WHITE
+                                    } else {
+                                        STAGE_LOCKED_COLOR
+                                    }),
+                                ));
+                            });
+                    }
+                });
+            parent.spawn((
+                Text::new(locale.tr(TextKey::StageSelectHelp)),
+                locale.text_font(14.),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+        });
+}
+
+// 关卡数据加载完成后生成缩略图
+pub fn update_stage_thumbnails(
+    mut commands: Commands,
+    levels_project: Res<LevelsProject>,
+    ldtk_projects: Res<Assets<LdtkProject>>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_thumbnails: Query<(Entity, &StageThumbnail, &mut Node), Without<ImageNode>>,
+) {
+    if q_thumbnails.is_empty() {
+        return;
+    }
+    let Some(project) = ldtk_projects.get(&levels_project.0) else {
+        return;
+    };
+    let levels: Vec<_> = project.iter_raw_levels().collect();
+    for (entity, thumbnail, mut node) in &mut q_thumbnails {
+        let Some(level) = levels.get(thumbnail.0) else {
+            continue;
+        };
+        let map = LevelMap::from_ldtk_level(level);
+        let size = thumbnail_size(&map);
+        node.width = Val::Px(size.x);
+        node.height = Val::Px(size.y);
+        let image = images.add(level_thumbnail(&map));
+        commands.entity(entity).insert(ImageNode::new(image));
+    }
+}
+
+// 缩略图按关卡实际尺寸保持宽高比，缩放到默认关卡尺寸的框内
+pub fn thumbnail_size(map: &LevelMap) -> Vec2 {
+    let frame = Vec2::new(LEVEL_COLUMNS as f32, LEVEL_ROWS as f32) * THUMBNAIL_TILE_PIXELS as f32;
+    let size = Vec2::new(map.width as f32, map.height as f32) * THUMBNAIL_TILE_PIXELS as f32;
+    size * (frame / size).min_element().min(1.)
+}
+
+// 用纯色像素绘制关卡缩略图
+pub fn level_thumbnail(map: &LevelMap) -> Image {
+    let width = map.width as u32 * THUMBNAIL_TILE_PIXELS;
+    let height = map.height as u32 * THUMBNAIL_TILE_PIXELS;
+    let mut data = vec![0; (width * height * 4) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let tile = map.get(
+                (x / THUMBNAIL_TILE_PIXELS) as i32,
+                (y / THUMBNAIL_TILE_PIXELS) as i32,
+            );
+            let color = thumbnail_color(tile).to_srgba().to_u8_array();
+            let offset = ((y * width + x) * 4) as usize;
+            data[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn thumbnail_color(tile: MapTile) -> Color {
+    match tile {
+        MapTile::Empty => Color::BLACK,
+        MapTile::StoneWall => Color::srgb(0.7, 0.3, 0.1),
+        MapTile::IronWall => Color::srgb(0.75, 0.75, 0.75),
+        MapTile::Water => Color::srgb(0.2, 0.3, 0.9),
+        MapTile::Tree => Color::srgb(0.2, 0.6, 0.1),
+        MapTile::Home => Color::srgb(1.0, 0.85, 0.0),
+        MapTile::Player1 => Color::srgb(0.9, 0.8, 0.3),
+        MapTile::Player2 => Color::srgb(0.3, 0.8, 0.4),
+        MapTile::Enemies => Color::srgb(0.9, 0.1, 0.1),
+    }
+}
+
+pub fn stage_select_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    unlocked_stages: Res<UnlockedStages>,
+    mut stage_select_cursor: ResMut<StageSelectCursor>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut custom_level: ResMut<CustomLevel>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut sound_ew: EventWriter<SoundEvent>,
+) {
+    let unlocked = unlocked_stages.0.min(MAX_LEVELS as usize);
+    if keyboard_input.any_just_pressed([KeyCode::ArrowRight, KeyCode::ArrowDown]) {
+        stage_select_cursor.0 = (stage_select_cursor.0 + 1) % unlocked;
+    } else if keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::ArrowUp]) {
+        stage_select_cursor.0 = (stage_select_cursor.0 + unlocked - 1) % unlocked;
+    } else if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        info!("Start from stage {}", stage_select_cursor.0 + 1);
+        *level_selection = LevelSelection::index(stage_select_cursor.0);
+        custom_level.0 = None;
+        app_state.set(AppState::Playing);
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_state.set(AppState::StartMenu);
+        return;
+    } else {
+        return;
+    }
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
+
+pub fn update_stage_select_screen(
+    stage_select_cursor: Res<StageSelectCursor>,
+    mut q_items: Query<(&StageSelectItem, &mut BorderColor)>,
+) {
+    for (item, mut border_color) in &mut q_items {
+        border_color.0 = if item.0 == stage_select_cursor.0 {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::NONE
+        };
+    }
+}
+
+// 到达新关卡时解锁
+pub fn unlock_reached_stage(
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    mut unlocked_stages: ResMut<UnlockedStages>,
+) {
+    if custom_level.0.is_some() {
+        return;
+    }
+    if let LevelSelection::Indices(LevelIndices { level, .. }) = *level_selection {
+        if level + 1 > unlocked_stages.0 {
+            info!("Unlock stage {}", level + 1);
+            unlocked_stages.0 = level + 1;
+            unlocked_stages.save();
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// 持久化存储：本地保存到系统配置目录，WASM保存到浏览器localStorage
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+const APP_NAME: &str = "battle-city";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn config_dir() -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let dir = if cfg!(target_os = "windows") {
+        env_dir("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env_dir("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    dir.map(|dir| dir.join(APP_NAME))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_text(name: &str) -> Option<String> {
+    std::fs::read_to_string(config_dir()?.join(name)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_text(name: &str, text: &str) -> Result<(), String> {
+    let dir = config_dir().ok_or("config directory not found")?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let path = dir.join(name);
+    std::fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(name: &str) {
+    if let Some(dir) = config_dir() {
+        let _ = std::fs::remove_file(dir.join(name));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_text(name: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("{}/{}", APP_NAME, name))
+        .ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_text(name: &str, text: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("localStorage not available")?
+        .set_item(&format!("{}/{}", APP_NAME, name), text)
+        .map_err(|_| format!("failed to write {} to localStorage", name))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(name: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(&format!("{}/{}", APP_NAME, name));
+    }
+}
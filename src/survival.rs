@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::cli::LaunchOptions;
+use crate::common::{MAX_LIVE_ENEMIES, TANK_SCALE, TANK_SIZE, TILE_SIZE};
+use crate::enemy::{choose_enemy_spawn, spawn_enemy, Enemy, EnemyKind};
+use crate::level::{EnemiesMarker, LevelItem};
+use crate::locale::{Locale, TextKey};
+use crate::map::{wall_bundle, MapTile};
+use crate::player::PlayerNo;
+use crate::sprites::SpriteAssets;
+use crate::tile_grid::TileGrid;
+
+// 两波敌人之间的休息时间，休息期间逐块修复家周围的砖墙
+const WAVE_BREAK_SECS: f32 = 4.0;
+const WALL_REGEN_SECS: f32 = 0.5;
+// 同时存活的敌人和每波敌人数量的上限
+const MAX_WAVE_LIVE_ENEMIES: u32 = 10;
+const MAX_WAVE_SIZE: u32 = 40;
+const MIN_SPAWN_INTERVAL: f32 = 0.75;
+
+// 生存模式：在同一张地图上无限波次地抵御敌人
+#[derive(Debug, Resource, Default)]
+pub struct SurvivalMode(pub bool);
+
+// 生存模式的波次进度，重新开始或离开对局时重置
+#[derive(Debug, Resource)]
+pub struct SurvivalWaves {
+    // 当前波次，0表示第一波开始前
+    pub wave: u32,
+    // 本波已生成的敌人数量
+    pub spawned: u32,
+    // 坚持的时间（秒），暂停时不计时
+    pub elapsed_secs: f32,
+    spawn_timer: Timer,
+    break_timer: Timer,
+    regen_timer: Timer,
+    // 对局开始时家周围砖墙的位置，找到家之前为None
+    base_walls: Option<Vec<Vec2>>,
+}
+
+impl Default for SurvivalWaves {
+    fn default() -> Self {
+        SurvivalWaves {
+            wave: 0,
+            spawned: 0,
+            elapsed_secs: 0.,
+            spawn_timer: Timer::from_seconds(wave_spawn_interval(1), TimerMode::Once),
+            break_timer: Timer::from_seconds(WAVE_BREAK_SECS, TimerMode::Once),
+            regen_timer: Timer::from_seconds(WALL_REGEN_SECS, TimerMode::Repeating),
+            base_walls: None,
+        }
+    }
+}
+
+impl SurvivalWaves {
+    // 本波还未被击毁的敌人数量
+    pub fn remaining(&self, live_enemies: u32) -> u32 {
+        wave_size(self.wave).saturating_sub(self.spawned) + live_enemies
+    }
+
+    fn between_waves(&self, live_enemies: u32) -> bool {
+        self.remaining(live_enemies) == 0
+    }
+}
+
+pub fn survival_enabled(survival_mode: Res<SurvivalMode>) -> bool {
+    survival_mode.0
+}
+
+// 每波敌人数量
+pub fn wave_size(wave: u32) -> u32 {
+    if wave == 0 {
+        return 0;
+    }
+    (6 + 2 * (wave - 1)).min(MAX_WAVE_SIZE)
+}
+
+// 同时存活的敌人数量上限，每两波加一
+pub fn wave_max_live_enemies(wave: u32) -> u32 {
+    (MAX_LIVE_ENEMIES as u32 + wave.saturating_sub(1) / 2).min(MAX_WAVE_LIVE_ENEMIES)
+}
+
+// 敌人生成间隔（秒）
+pub fn wave_spawn_interval(wave: u32) -> f32 {
+    (3.0 - 0.25 * wave.saturating_sub(1) as f32).max(MIN_SPAWN_INTERVAL)
+}
+
+// roll为[0, 1)的随机数，波次越高快速、火力和装甲坦克的比例越高
+pub fn wave_enemy_kind(wave: u32, roll: f32) -> EnemyKind {
+    let share =
+        |start: u32, step: f32, max: f32| (wave.saturating_sub(start) as f32 * step).min(max);
+    let armor = share(3, 0.05, 0.2);
+    let power = armor + share(2, 0.07, 0.25);
+    let fast = power + share(1, 0.1, 0.3);
+    if roll < armor {
+        EnemyKind::Armor
+    } else if roll < power {
+        EnemyKind::Power
+    } else if roll < fast {
+        EnemyKind::Fast
+    } else {
+        EnemyKind::Basic
+    }
+}
+
+// 分:秒
+pub fn format_survival_time(secs: u32) -> String {
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+pub fn survival_hud_text(locale: &Locale, waves: &SurvivalWaves, live_enemies: u32) -> String {
+    format!(
+        "{} {}\n{} {}\n{} {}\n",
+        locale.tr(TextKey::Wave),
+        waves.wave,
+        locale.tr(TextKey::Time),
+        format_survival_time(waves.elapsed_secs as u32),
+        locale.tr(TextKey::Enemies),
+        waves.remaining(live_enemies),
+    )
+}
+
+// 家周围一格内的砖墙
+fn base_walls(tile_grid: &TileGrid) -> Option<Vec<Vec2>> {
+    let (_, home) = tile_grid
+        .iter()
+        .find(|(level_item, _)| **level_item == LevelItem::Home)?;
+    Some(
+        tile_grid
+            .iter()
+            .filter(|(level_item, pos)| {
+                **level_item == LevelItem::StoneWall
+                    && (*pos - home).abs().max_element() < 1.5 * TILE_SIZE
+            })
+            .map(|(_, pos)| pos)
+            .collect(),
+    )
+}
+
+// 被毁且没有坦克压住的家周围砖墙
+fn missing_base_wall(base_walls: &[Vec2], tile_grid: &TileGrid, tanks: &[Vec3]) -> Option<Vec2> {
+    let half_tile = Vec2::splat(TILE_SIZE / 2.);
+    let clearance = (TILE_SIZE + TANK_SIZE as f32 * TANK_SCALE) / 2.;
+    base_walls.iter().copied().find(|pos| {
+        !tile_grid
+            .items_in(*pos - half_tile, *pos + half_tile)
+            .any(|(level_item, _)| *level_item == LevelItem::StoneWall)
+            && tanks
+                .iter()
+                .all(|tank| (tank.truncate() - *pos).abs().max_element() >= clearance)
+    })
+}
+
+// 计时、按波次生成敌人，两波之间修复家周围的砖墙
+pub fn update_survival_waves(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut waves: ResMut<SurvivalWaves>,
+    tile_grid: Res<TileGrid>,
+    q_enemies: Query<&Transform, With<Enemy>>,
+    q_players: Query<&Transform, With<PlayerNo>>,
+    q_enemies_marker: Query<&GlobalTransform, With<EnemiesMarker>>,
+    sprite_assets: Res<SpriteAssets>,
+) {
+    waves.elapsed_secs += time.delta_secs();
+    if waves.base_walls.is_none() {
+        waves.base_walls = base_walls(&tile_grid);
+    }
+    let live_enemies = q_enemies.iter().len() as u32;
+    let tanks: Vec<Vec3> = q_enemies
+        .iter()
+        .chain(&q_players)
+        .map(|transform| transform.translation)
+        .collect();
+
+    if waves.between_waves(live_enemies) {
+        if waves.regen_timer.tick(time.delta()).just_finished() {
+            let missing = waves
+                .base_walls
+                .as_deref()
+                .and_then(|base_walls| missing_base_wall(base_walls, &tile_grid, &tanks));
+            if let Some(pos) = missing {
+                commands.spawn(wall_bundle(
+                    MapTile::StoneWall,
+                    pos.extend(0.),
+                    &sprite_assets,
+                ));
+            }
+        }
+        if waves.break_timer.tick(time.delta()).just_finished() {
+            waves.wave += 1;
+            waves.spawned = 0;
+            waves.spawn_timer =
+                Timer::from_seconds(wave_spawn_interval(waves.wave), TimerMode::Once);
+            waves.break_timer.reset();
+            waves.regen_timer.reset();
+            info!(
+                "Survival wave {} starts, enemies={}",
+                waves.wave,
+                wave_size(waves.wave)
+            );
+        }
+        return;
+    }
+
+    if waves.spawned >= wave_size(waves.wave) || live_enemies >= wave_max_live_enemies(waves.wave) {
+        return;
+    }
+    if !waves.spawn_timer.tick(time.delta()).finished() {
+        return;
+    }
+    let marker_positions: Vec<Vec3> = q_enemies_marker
+        .iter()
+        .map(GlobalTransform::translation)
+        .collect();
+    // 出生点被占用时下一帧重试
+    if let Some(pos) = choose_enemy_spawn(&marker_positions, &tanks) {
+        let kind = wave_enemy_kind(waves.wave, rand::thread_rng().gen());
+        spawn_enemy(pos, kind, &mut commands, &sprite_assets);
+        waves.spawned += 1;
+        waves.spawn_timer.reset();
+    }
+}
+
+pub fn reset_survival_waves(mut waves: ResMut<SurvivalWaves>) {
+    *waves = SurvivalWaves::default();
+}
+
+pub fn reset_survival_mode(
+    mut survival_mode: ResMut<SurvivalMode>,
+    launch_options: Res<LaunchOptions>,
+) {
+    survival_mode.0 = launch_options.survival;
+}
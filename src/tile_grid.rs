@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::common::TILE_SIZE;
+use crate::level::LevelItem;
+
+// 按格子索引的地图元素，随地图元素生成和销毁同步更新，用于快速查询某块区域内的元素
+// 目前用于敌人移动的障碍检测、生存模式重建基地砖墙和小地图
+#[derive(Debug, Resource, Default)]
+pub struct TileGrid {
+    cells: HashMap<IVec2, Vec<(Entity, LevelItem, Vec2)>>,
+    // 实体所在格子，移除时使用
+    entities: HashMap<Entity, IVec2>,
+}
+
+impl TileGrid {
+    // 世界坐标所在格子
+    pub fn cell(pos: Vec2) -> IVec2 {
+        (pos / TILE_SIZE).floor().as_ivec2()
+    }
+
+    pub fn insert(&mut self, entity: Entity, item: LevelItem, pos: Vec2) {
+        self.remove(entity);
+        let cell = Self::cell(pos);
+        self.cells
+            .entry(cell)
+            .or_default()
+            .push((entity, item, pos));
+        self.entities.insert(entity, cell);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some(cell) = self.entities.remove(&entity) else {
+            return;
+        };
+        if let Some(items) = self.cells.get_mut(&cell) {
+            items.retain(|(e, _, _)| *e != entity);
+            if items.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&LevelItem, Vec2)> {
+        self.cells
+            .values()
+            .flatten()
+            .map(|(_, item, pos)| (item, *pos))
+    }
+
+    // 中心点在矩形内（不含边界）的地图元素
+    pub fn items_in(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (&LevelItem, Vec2)> {
+        let min_cell = Self::cell(min);
+        let max_cell = Self::cell(max);
+        (min_cell.y..=max_cell.y)
+            .flat_map(move |y| (min_cell.x..=max_cell.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(_, _, pos)| {
+                pos.x > min.x && pos.x < max.x && pos.y > min.y && pos.y < max.y
+            })
+            .map(|(_, item, pos)| (item, *pos))
+    }
+
+    // 矩形内是否有坦克不能通过的地图元素
+    pub fn is_blocked(&self, min: Vec2, max: Vec2) -> bool {
+        self.items_in(min, max)
+            .any(|(item, _)| *item != LevelItem::Tree)
+    }
+}
+
+// 变换传播后GlobalTransform发生变化，此时才能得到地图元素的世界坐标
+pub fn update_tile_grid(
+    mut tile_grid: ResMut<TileGrid>,
+    q_level_items: Query<(Entity, &LevelItem, &GlobalTransform), Changed<GlobalTransform>>,
+    mut removed_level_items: RemovedComponents<LevelItem>,
+) {
+    for entity in removed_level_items.read() {
+        tile_grid.remove(entity);
+    }
+    for (entity, level_item, transform) in &q_level_items {
+        tile_grid.insert(
+            entity,
+            level_item.clone(),
+            transform.translation().truncate(),
+        );
+    }
+}
@@ -1,30 +1,174 @@
-use crate::common::{AppState, GameSounds, MultiplayerMode, SPRITE_GAME_OVER_ORDER, TANK_SIZE};
+use crate::audio::{Sound, SoundEvent};
+use crate::camera::{GameCamera, SplitScreen, ViewportHudText};
+use crate::cli::LaunchOptions;
+use crate::common::{
+    AppState, MultiplayerMode, ENEMIES_PER_LEVEL, LEVEL_COLUMNS, LEVEL_ROWS, SPRITE_GAME_OVER_ORDER,
+};
+use crate::editor::EditorTestPlay;
+use crate::enemy::{Enemy, LevelSpawnedEnemies};
+use crate::generator::{generate_level, GeneratedLevel};
+use crate::high_score::{
+    spawn_initials_entry, InitialsEntry, OnInitialsEntryScreen, PendingHighScore,
+};
+use crate::level::stage_number;
+use crate::locale::{Locale, TextKey};
+use crate::map::{CustomLevel, LevelPack};
+use crate::player::{PlayerLives, PlayerScores};
+use crate::save_game::SaveSlot;
+use crate::settings::OptionsMenu;
+use crate::sprites::SpriteAssets;
+use crate::survival::{survival_hud_text, SurvivalMode, SurvivalWaves};
 use bevy::prelude::*;
-use std::time::Duration;
+use bevy_ecs_ldtk::prelude::*;
+
+pub const MENU_SELECTED_COLOR: Color = Color::srgb(1.0, 0.8, 0.0);
+const GAME_OVER_COLOR: Color = Color::srgb(0.85, 0.2, 0.1);
+
+// 开始菜单标题和行距
+const TITLE_FONT_SIZE: f32 = 80.;
+const TITLE_COLOR: Color = Color::srgb(0.85, 0.35, 0.1);
+const START_MENU_LINE_HEIGHT: f32 = 32.;
 
 #[derive(Component)]
 pub struct OnStartMenuScreen;
+// 开始菜单每一行前的坦克光标，只显示选中行
 #[derive(Component)]
-pub struct OnStartMenuScreenMultiplayerModeFlag;
+pub struct StartMenuCursorIcon(pub usize);
+
+// 开始菜单选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartMenuItem {
+    OnePlayer,
+    TwoPlayers,
+    LanGame,
+    StageSelect,
+    Construction,
+    RandomMap,
+    Survival,
+    HighScores,
+    Options,
+    Continue,
+}
+
+impl StartMenuItem {
+    pub const ALL: [StartMenuItem; 10] = [
+        StartMenuItem::OnePlayer,
+        StartMenuItem::TwoPlayers,
+        StartMenuItem::LanGame,
+        StartMenuItem::StageSelect,
+        StartMenuItem::Construction,
+        StartMenuItem::RandomMap,
+        StartMenuItem::Survival,
+        StartMenuItem::HighScores,
+        StartMenuItem::Options,
+        StartMenuItem::Continue,
+    ];
+
+    pub fn label(self) -> TextKey {
+        match self {
+            StartMenuItem::OnePlayer => TextKey::OnePlayer,
+            StartMenuItem::TwoPlayers => TextKey::TwoPlayers,
+            StartMenuItem::LanGame => TextKey::LanGame,
+            StartMenuItem::StageSelect => TextKey::StageSelect,
+            StartMenuItem::Construction => TextKey::Construction,
+            StartMenuItem::RandomMap => TextKey::RandomMap,
+            StartMenuItem::Survival => TextKey::Survival,
+            StartMenuItem::HighScores => TextKey::HighScores,
+            StartMenuItem::Options => TextKey::Options,
+            StartMenuItem::Continue => TextKey::Continue,
+        }
+    }
+
+    // 没有存档时不显示CONTINUE
+    pub fn available(self, save_slot: &SaveSlot) -> bool {
+        self != StartMenuItem::Continue || save_slot.0.is_some()
+    }
+}
+
+// 开始菜单当前选中项
+#[derive(Debug, Resource, Default)]
+pub struct StartMenuCursor(pub usize);
+
+impl StartMenuCursor {
+    pub fn item(&self) -> StartMenuItem {
+        StartMenuItem::ALL[self.0]
+    }
+}
+
+// 对局中显示在战场右侧的关卡、敌人、生命和得分
+#[derive(Component)]
+pub struct OnHudScreen;
+#[derive(Component)]
+pub struct HudText;
+
+#[derive(Component)]
+pub struct OnPauseScreen;
+#[derive(Component)]
+pub struct PauseMenuText(pub usize);
+
+// 暂停菜单选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuItem {
+    Resume,
+    RestartStage,
+    Options,
+    QuitToMenu,
+}
+
+impl PauseMenuItem {
+    pub const ALL: [PauseMenuItem; 4] = [
+        PauseMenuItem::Resume,
+        PauseMenuItem::RestartStage,
+        PauseMenuItem::Options,
+        PauseMenuItem::QuitToMenu,
+    ];
+
+    pub fn label(self) -> TextKey {
+        match self {
+            PauseMenuItem::Resume => TextKey::Resume,
+            PauseMenuItem::RestartStage => TextKey::RestartStage,
+            PauseMenuItem::Options => TextKey::Options,
+            PauseMenuItem::QuitToMenu => TextKey::QuitToMenu,
+        }
+    }
+}
+
+#[derive(Debug, Resource, Default)]
+pub struct PauseMenuCursor(pub usize);
+
+impl PauseMenuCursor {
+    pub fn item(&self) -> PauseMenuItem {
+        PauseMenuItem::ALL[self.0]
+    }
+}
 
 #[derive(Component)]
 pub struct OnGameOverScreen;
+#[derive(Component)]
+pub struct OnVictoryScreen;
+#[derive(Component)]
+pub struct GameOverText;
+#[derive(Component)]
+pub struct GameOverMenuItem(pub usize);
+
+// 游戏结束菜单选项
+pub const GAME_OVER_MENU_ITEMS: [TextKey; 2] = [TextKey::ContinueThisStage, TextKey::StartMenu];
+
+#[derive(Debug, Resource, Default)]
+pub struct GameOverCursor(pub usize);
 
 pub fn setup_start_menu(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprite_assets: Res<SpriteAssets>,
+    save_slot: Res<SaveSlot>,
+    locale: Res<Locale>,
 ) {
-    let player1_texture_handle = asset_server.load("textures/tank1.bmp");
-    let player1_texture_atlas =
-        TextureAtlasLayout::from_grid(UVec2::new(TANK_SIZE, TANK_SIZE), 8, 4, None, None);
-    let player1_atlas_layout_handle = atlas_layouts.add(player1_texture_atlas);
-
     commands
         .spawn((
             Node {
                 width: Val::Percent(100.),
                 height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 ..default()
@@ -32,157 +176,569 @@ pub fn setup_start_menu(
             OnStartMenuScreen,
         ))
         .with_children(|parent| {
-            parent.spawn(ImageNode {
-                image: asset_server.load("textures/title.bmp").into(),
-                ..default()
-            });
             parent.spawn((
+                Text::new(locale.tr(TextKey::Title)),
+                locale.text_font(TITLE_FONT_SIZE),
+                TextColor(TITLE_COLOR),
+                TextLayout::new_with_justify(JustifyText::Center),
                 Node {
-                    width: Val::Px(20.),
-                    height: Val::Px(20.),
-                    margin: UiRect::all(Val::Px(10.0)),
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(412.),
-                    left: Val::Px(520.),
-                    ..default()
-                },
-                ImageNode {
-                    image: player1_texture_handle,
-                    texture_atlas: Some(TextureAtlas {
-                        index: 0,
-                        layout: player1_atlas_layout_handle,
-                    }),
+                    margin: UiRect::bottom(Val::Px(40.)),
                     ..default()
                 },
-                OnStartMenuScreenMultiplayerModeFlag,
             ));
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (i, item) in StartMenuItem::ALL.iter().enumerate() {
+                        if !item.available(&save_slot) {
+                            continue;
+                        }
+                        parent
+                            .spawn(Node {
+                                height: Val::Px(START_MENU_LINE_HEIGHT),
+                                align_items: AlignItems::Center,
+                                column_gap: Val::Px(16.),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Node {
+                                        width: Val::Px(20.),
+                                        height: Val::Px(20.),
+                                        ..default()
+                                    },
+                                    ImageNode {
+                                        image: sprite_assets.player1.clone(),
+                                        texture_atlas: Some(TextureAtlas {
+                                            index: 0,
+                                            layout: sprite_assets.tank_layout.clone(),
+                                        }),
+                                        ..default()
+                                    },
+                                    Visibility::Hidden,
+                                    StartMenuCursorIcon(i),
+                                ));
+                                parent.spawn((
+                                    Text::new(locale.tr(item.label())),
+                                    locale.text_font(20.),
+                                    TextColor(Color::WHITE),
+                                ));
+                            });
+                    }
+                });
         });
-    commands.spawn((
-        AudioPlayer::new(asset_server.load("sounds/start_menu.ogg")),
-        PlaybackSettings::DESPAWN,
-    ));
 }
 
 pub fn setup_game_over(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    game_sounds: Res<GameSounds>,
+    locale: Res<Locale>,
+    mut game_over_cursor: ResMut<GameOverCursor>,
+    q_camera: Query<Entity, With<GameCamera>>,
 ) {
-    let game_over_texture = asset_server.load("textures/game_over.bmp");
-    commands.spawn((
-        Sprite {
-            image: game_over_texture,
-            ..default()
-        },
-        Transform::from_translation(Vec3::new(0., -400., SPRITE_GAME_OVER_ORDER)),
-        OnGameOverScreen,
-    ));
-    commands.spawn((
-        AudioPlayer(game_sounds.game_over.clone()),
-        PlaybackSettings::DESPAWN,
-    ));
+    let game_over_text = commands
+        .spawn((
+            Text2d::new(locale.tr(TextKey::GameOver)),
+            locale.text_font(48.),
+            TextColor(GAME_OVER_COLOR),
+            TextLayout::new_with_justify(JustifyText::Center),
+            Transform::from_translation(Vec3::new(0., -400., SPRITE_GAME_OVER_ORDER)),
+            OnGameOverScreen,
+            GameOverText,
+        ))
+        .id();
+    // 镜头跟随玩家时文字相对镜头移动
+    if let Ok(camera) = q_camera.get_single() {
+        commands.entity(camera).add_child(game_over_text);
+    }
+    game_over_cursor.0 = 0;
 }
 
 pub fn animate_game_over(
-    mut q_game_over: Query<&mut Transform, With<OnGameOverScreen>>,
+    mut commands: Commands,
+    mut q_game_over: Query<&mut Transform, With<GameOverText>>,
+    q_game_over_menu: Query<(), With<GameOverMenuItem>>,
     mut app_state: ResMut<NextState<AppState>>,
     time: Res<Time>,
     mut stop_secs: Local<f32>,
+    editor_test_play: Res<EditorTestPlay>,
+    locale: Res<Locale>,
+    q_initials_entry: Query<(), With<OnInitialsEntryScreen>>,
+    pending_high_score: Res<PendingHighScore>,
+    initials_entry: Res<InitialsEntry>,
 ) {
     for mut transform in &mut q_game_over {
-        // 上移game over图片
+        // 上移game over文字
         if transform.translation.y < 0. {
             transform.translation.y += time.delta_secs() * 150.;
             *stop_secs = 0.0;
         } else {
-            // 停顿1秒后，切换到Start Menu
+            // 停顿1秒后显示菜单，编辑器试玩时直接返回编辑器，上榜时先输入名字
             *stop_secs += time.delta_secs();
             if *stop_secs > 1.0 {
-                app_state.set(AppState::StartMenu);
+                if editor_test_play.0 {
+                    app_state.set(AppState::Editor);
+                } else if let Some(entry) = &pending_high_score.0 {
+                    if q_initials_entry.is_empty() {
+                        spawn_initials_entry(&mut commands, &locale, entry, &initials_entry);
+                    }
+                } else if q_game_over_menu.is_empty() {
+                    spawn_game_over_menu(&mut commands, &locale);
+                }
             }
         }
     }
 }
 
-pub fn start_game(
+fn spawn_game_over_menu(commands: &mut Commands, locale: &Locale) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(80.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
+                ..default()
+            },
+            OnGameOverScreen,
+        ))
+        .with_children(|parent| {
+            for (i, label) in GAME_OVER_MENU_ITEMS.iter().enumerate() {
+                parent.spawn((
+                    Text::new(locale.tr(*label)),
+                    locale.text_font(22.),
+                    TextColor(Color::WHITE),
+                    GameOverMenuItem(i),
+                ));
+            }
+        });
+}
+
+// 游戏结束菜单：从当前关卡继续（重置生命和得分）或返回开始菜单
+pub fn game_over_menu(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut game_over_cursor: ResMut<GameOverCursor>,
+    mut q_game_over_menu: Query<(&GameOverMenuItem, &mut TextColor)>,
     mut app_state: ResMut<NextState<AppState>>,
+    mut sound_ew: EventWriter<SoundEvent>,
 ) {
+    if q_game_over_menu.is_empty() {
+        return;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::ArrowDown]) {
+        game_over_cursor.0 = (game_over_cursor.0 + 1) % GAME_OVER_MENU_ITEMS.len();
+        sound_ew.send(SoundEvent(Sound::ModeSwitch));
+    }
+    for (item, mut text_color) in &mut q_game_over_menu {
+        text_color.0 = if item.0 == game_over_cursor.0 {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
     if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
-        info!("Switch app state to playing");
-        app_state.set(AppState::Playing);
+        if game_over_cursor.0 == 0 {
+            info!("Continue from current stage");
+            app_state.set(AppState::Playing);
+        } else {
+            app_state.set(AppState::StartMenu);
+        }
     }
 }
 
-pub fn switch_multiplayer_mode(
+// 全部通关，上榜时在下方输入名字
+pub fn setup_victory(
     mut commands: Commands,
+    locale: Res<Locale>,
+    player_scores: Res<PlayerScores>,
+    pending_high_score: Res<PendingHighScore>,
+    initials_entry: Res<InitialsEntry>,
+) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(20.),
+                ..default()
+            },
+            OnVictoryScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::Victory)),
+                locale.text_font(64.),
+                TextColor(MENU_SELECTED_COLOR),
+            ));
+            parent.spawn((
+                Text::new(format!(
+                    "{} {}",
+                    locale.tr(TextKey::Score),
+                    player_scores.player1 + player_scores.player2
+                )),
+                locale.text_font(28.),
+            ));
+            parent.spawn((
+                Text::new(locale.tr(TextKey::PressEnterToReturn)),
+                locale.text_font(16.),
+            ));
+        });
+    if let Some(entry) = &pending_high_score.0 {
+        spawn_initials_entry(&mut commands, &locale, entry, &initials_entry);
+    }
+}
+
+// 输入名字后按回车返回开始菜单
+pub fn victory_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut multiplayer_mode: ResMut<MultiplayerMode>,
-    mut q_multiplayer_mode_flag: Query<&mut Node, With<OnStartMenuScreenMultiplayerModeFlag>>,
-    game_sounds: Res<GameSounds>,
+    q_initials_entry: Query<(), With<OnInitialsEntryScreen>>,
+    mut app_state: ResMut<NextState<AppState>>,
 ) {
-    if keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::ArrowDown]) {
-        for mut node in &mut q_multiplayer_mode_flag {
-            if *multiplayer_mode == MultiplayerMode::SinglePlayer {
-                node.top = Val::Px(440.);
-                *multiplayer_mode = MultiplayerMode::TwoPlayers;
-            } else if *multiplayer_mode == MultiplayerMode::TwoPlayers {
-                node.top = Val::Px(412.);
-                *multiplayer_mode = MultiplayerMode::SinglePlayer;
+    if q_initials_entry.is_empty()
+        && keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+    {
+        app_state.set(AppState::StartMenu);
+    }
+}
+
+pub fn start_game(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    start_menu_cursor: Res<StartMenuCursor>,
+    mut custom_level: ResMut<CustomLevel>,
+    mut generated_level: ResMut<GeneratedLevel>,
+    mut survival_mode: ResMut<SurvivalMode>,
+    mut options_menu: ResMut<OptionsMenu>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        match start_menu_cursor.item() {
+            StartMenuItem::OnePlayer | StartMenuItem::TwoPlayers => {
+                info!("Switch app state to playing");
+                app_state.set(AppState::Playing);
             }
-            commands.spawn((
-                AudioPlayer(game_sounds.mode_switch.clone()),
-                PlaybackSettings::DESPAWN,
-            ));
+            StartMenuItem::LanGame => {
+                app_state.set(AppState::Lobby);
+            }
+            StartMenuItem::StageSelect => {
+                app_state.set(AppState::StageSelect);
+            }
+            StartMenuItem::Construction => {
+                info!("Switch app state to editor");
+                app_state.set(AppState::Editor);
+            }
+            StartMenuItem::RandomMap => {
+                let seed = rand::random::<u64>();
+                info!("Generate random level, seed={}", seed);
+                // 默认尺寸总是满足随机关卡的最小尺寸
+                custom_level.0 = generate_level(seed, LEVEL_COLUMNS, LEVEL_ROWS).ok();
+                generated_level.0 = Some(seed);
+                app_state.set(AppState::Playing);
+            }
+            StartMenuItem::Survival => {
+                // 生存模式在一张随机地图上进行，不切换关卡
+                let seed = rand::random::<u64>();
+                info!("Start survival mode, seed={}", seed);
+                custom_level.0 = generate_level(seed, LEVEL_COLUMNS, LEVEL_ROWS).ok();
+                survival_mode.0 = true;
+                app_state.set(AppState::Playing);
+            }
+            StartMenuItem::HighScores => {
+                app_state.set(AppState::HighScores);
+            }
+            StartMenuItem::Options => {
+                options_menu.return_state = AppState::StartMenu;
+                app_state.set(AppState::Options);
+            }
+            // 由 continue_saved_game 读取存档
+            StartMenuItem::Continue => {}
         }
     }
 }
 
+pub fn switch_multiplayer_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut multiplayer_mode: ResMut<MultiplayerMode>,
+    mut start_menu_cursor: ResMut<StartMenuCursor>,
+    mut sound_ew: EventWriter<SoundEvent>,
+    save_slot: Res<SaveSlot>,
+) {
+    let items = StartMenuItem::ALL.len();
+    let step = if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        items - 1
+    } else {
+        return;
+    };
+    // 跳过不可用的选项
+    start_menu_cursor.0 = (start_menu_cursor.0 + step) % items;
+    while !start_menu_cursor.item().available(&save_slot) {
+        start_menu_cursor.0 = (start_menu_cursor.0 + step) % items;
+    }
+    match start_menu_cursor.item() {
+        StartMenuItem::OnePlayer => *multiplayer_mode = MultiplayerMode::SinglePlayer,
+        StartMenuItem::TwoPlayers => *multiplayer_mode = MultiplayerMode::TwoPlayers,
+        _ => {}
+    }
+    sound_ew.send(SoundEvent(Sound::ModeSwitch));
+}
+
+pub fn update_start_menu_cursor(
+    start_menu_cursor: Res<StartMenuCursor>,
+    mut q_cursor_icons: Query<(&StartMenuCursorIcon, &mut Visibility)>,
+) {
+    for (icon, mut visibility) in &mut q_cursor_icons {
+        let target = if icon.0 == start_menu_cursor.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        visibility.set_if_neq(target);
+    }
+}
+
 pub fn pause_game(
-    mut commands: Commands,
     mut app_state: ResMut<NextState<AppState>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    game_sounds: Res<GameSounds>,
-    mut cold_start: Local<Duration>,
-    time: Res<Time>,
 ) {
-    // 增加冷启动防止 pause_game 和 unpause_game 都会收到input，导致Paued<->Playing不断循环
-    *cold_start += time.delta();
-    if cold_start.as_millis() > 100 {
-        if keyboard_input.just_released(KeyCode::Escape) {
-            info!("Pause game");
-            commands.spawn((
-                AudioPlayer(game_sounds.game_pause.clone()),
-                PlaybackSettings::DESPAWN,
-            ));
-            app_state.set(AppState::Paused);
-            *cold_start = Duration::ZERO;
-        }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        info!("Pause game");
+        app_state.set(AppState::Paused);
     }
 }
 
-pub fn unpause_game(
-    mut app_state: ResMut<NextState<AppState>>,
+pub fn setup_pause_menu(
+    mut commands: Commands,
+    locale: Res<Locale>,
+    mut pause_menu_cursor: ResMut<PauseMenuCursor>,
+) {
+    pause_menu_cursor.0 = 0;
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.6)),
+            OnPauseScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(locale.tr(TextKey::Paused)),
+                locale.text_font(36.),
+                Node {
+                    margin: UiRect::bottom(Val::Px(20.)),
+                    ..default()
+                },
+            ));
+            for (i, item) in PauseMenuItem::ALL.iter().enumerate() {
+                parent.spawn((
+                    Text::new(locale.tr(item.label())),
+                    locale.text_font(22.),
+                    TextColor(Color::WHITE),
+                    PauseMenuText(i),
+                ));
+            }
+        });
+}
+
+// 暂停菜单，Esc继续游戏，退出到开始菜单时自动存档
+pub fn pause_menu(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut cold_start: Local<Duration>,
-    time: Res<Time>,
+    mut pause_menu_cursor: ResMut<PauseMenuCursor>,
+    mut q_pause_menu: Query<(&PauseMenuText, &mut TextColor)>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut options_menu: ResMut<OptionsMenu>,
+    editor_test_play: Res<EditorTestPlay>,
+    mut sound_ew: EventWriter<SoundEvent>,
 ) {
-    *cold_start += time.delta();
-    if cold_start.as_millis() > 100 {
-        if keyboard_input.just_released(KeyCode::Escape) {
+    let items = PauseMenuItem::ALL.len();
+    let step = if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        items - 1
+    } else {
+        0
+    };
+    if step > 0 {
+        pause_menu_cursor.0 = (pause_menu_cursor.0 + step) % items;
+        sound_ew.send(SoundEvent(Sound::ModeSwitch));
+    }
+    for (text, mut text_color) in &mut q_pause_menu {
+        text_color.0 = if text.0 == pause_menu_cursor.0 {
+            MENU_SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        info!("Unpause game");
+        app_state.set(AppState::Playing);
+        return;
+    }
+    if !keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        return;
+    }
+    match pause_menu_cursor.item() {
+        PauseMenuItem::Resume => {
             info!("Unpause game");
             app_state.set(AppState::Playing);
-            *cold_start = Duration::ZERO;
+        }
+        PauseMenuItem::RestartStage => {
+            info!("Restart stage");
+            app_state.set(AppState::Restarting);
+        }
+        PauseMenuItem::Options => {
+            options_menu.return_state = AppState::Paused;
+            app_state.set(AppState::Options);
+        }
+        PauseMenuItem::QuitToMenu => {
+            app_state.set(if editor_test_play.0 {
+                AppState::Editor
+            } else {
+                AppState::StartMenu
+            });
         }
     }
 }
 
+// 清理完当前关卡后重新进入游戏
+pub fn restart_stage(mut app_state: ResMut<NextState<AppState>>) {
+    app_state.set(AppState::Playing);
+}
+
 pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
         commands.entity(entity).despawn_recursive();
     }
 }
 
-pub fn reset_multiplayer_mode(mut multiplayer_mode: ResMut<MultiplayerMode>) {
-    *multiplayer_mode = MultiplayerMode::SinglePlayer;
+pub fn reset_multiplayer_mode(
+    mut multiplayer_mode: ResMut<MultiplayerMode>,
+    mut start_menu_cursor: ResMut<StartMenuCursor>,
+    launch_options: Res<LaunchOptions>,
+) {
+    *multiplayer_mode = launch_options
+        .players
+        .unwrap_or(MultiplayerMode::SinglePlayer);
+    start_menu_cursor.0 = match *multiplayer_mode {
+        MultiplayerMode::SinglePlayer => 0,
+        MultiplayerMode::TwoPlayers => 1,
+    };
+}
+
+pub fn setup_hud(mut commands: Commands, locale: Res<Locale>, q_hud: Query<(), With<OnHudScreen>>) {
+    // 从暂停恢复时HUD仍在
+    if !q_hud.is_empty() {
+        return;
+    }
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.),
+            right: Val::Px(30.),
+            ..default()
+        },
+        Text::default(),
+        locale.text_font(18.),
+        TextColor(Color::WHITE),
+        OnHudScreen,
+        HudText,
+    ));
+}
+
+// 联机客户端使用主机发送的数据生成同样的HUD
+pub fn hud_text(locale: &Locale, header: String, players: &[(&str, i8, u32)]) -> String {
+    let mut text = header;
+    for (name, lives, score) in players {
+        text += "\n";
+        text += &player_hud_text(locale, name, *lives, *score);
+    }
+    text
+}
+
+pub fn stage_hud_text(locale: &Locale, stage: usize, enemies: i32) -> String {
+    format!(
+        "{} {}\n{} {}\n",
+        locale.tr(TextKey::Stage),
+        stage,
+        locale.tr(TextKey::Enemies),
+        enemies,
+    )
+}
+
+// 单个玩家的HUD，分屏时显示在各自画面内
+pub fn player_hud_text(locale: &Locale, name: &str, lives: i8, score: u32) -> String {
+    format!(
+        "{}\n{} {}\n{} {}\n",
+        name,
+        locale.tr(TextKey::Lives),
+        lives.max(0),
+        locale.tr(TextKey::Score),
+        score,
+    )
+}
+
+pub fn update_hud(
+    locale: Res<Locale>,
+    level_selection: Res<LevelSelection>,
+    custom_level: Res<CustomLevel>,
+    level_pack: Res<LevelPack>,
+    level_spawned_enemies: Res<LevelSpawnedEnemies>,
+    q_enemies: Query<(), With<Enemy>>,
+    multiplayer_mode: Res<MultiplayerMode>,
+    player_lives: Res<PlayerLives>,
+    player_scores: Res<PlayerScores>,
+    mut q_hud_text: Query<&mut Text, With<HudText>>,
+    split_screen: Res<SplitScreen>,
+    mut q_viewport_hud_text: Query<(&mut Text, &ViewportHudText), Without<HudText>>,
+    survival_mode: Res<SurvivalMode>,
+    survival_waves: Res<SurvivalWaves>,
+) {
+    let live_enemies = q_enemies.iter().len();
+    // 生存模式显示波次和坚持的时间
+    let header = if survival_mode.0 {
+        survival_hud_text(&locale, &survival_waves, live_enemies as u32)
+    } else {
+        stage_hud_text(
+            &locale,
+            stage_number(&level_selection, &custom_level, &level_pack),
+            ENEMIES_PER_LEVEL - level_spawned_enemies.0 + live_enemies as i32,
+        )
+    };
+    let mut players = vec![("1P", player_lives.player1, player_scores.player1)];
+    if *multiplayer_mode == MultiplayerMode::TwoPlayers {
+        players.push(("2P", player_lives.player2, player_scores.player2));
+    }
+    for (mut text, viewport_hud_text) in &mut q_viewport_hud_text {
+        let (name, lives, score) = players[viewport_hud_text.0 .0 as usize - 1];
+        let player_text = player_hud_text(&locale, name, lives, score);
+        if text.0 != player_text {
+            text.0 = player_text;
+        }
+    }
+    // 分屏时玩家信息显示在各自画面内
+    if split_screen.0 {
+        players.clear();
+    }
+    let text = hud_text(&locale, header, &players);
+    for mut hud_text in &mut q_hud_text {
+        if hud_text.0 != text {
+            hud_text.0.clone_from(&text);
+        }
+    }
 }
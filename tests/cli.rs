@@ -0,0 +1,51 @@
+use battle_city::{cli::*, map::MapTile};
+
+fn parse(args: &[&str]) -> Result<LaunchOptions, String> {
+    LaunchOptions::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn ldtk_level_count_reads_levels() {
+    let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/levels.ldtk"))
+        .unwrap();
+    assert_eq!(ldtk_level_count(&text), Ok(2));
+    assert!(ldtk_level_count("{}").is_err());
+}
+
+#[test]
+fn start_level_is_checked_against_ldtk_levels() {
+    assert!(parse(&["--level", "1"])
+        .unwrap()
+        .validate_ldtk_start_level()
+        .is_ok());
+    assert!(parse(&["--level", "2"])
+        .unwrap()
+        .validate_ldtk_start_level()
+        .is_err());
+    assert!(parse(&["--levels", "assets/levels.ldtk", "--level", "5"])
+        .unwrap()
+        .validate_ldtk_start_level()
+        .is_err());
+    // 自定义关卡文件在读取关卡包时检查
+    assert!(parse(&["--levels", "pack", "--level", "5"])
+        .unwrap()
+        .validate_ldtk_start_level()
+        .is_ok());
+}
+
+#[test]
+fn headless_converts_ldtk_levels() {
+    let maps = parse(&["--headless"]).unwrap().load_level_pack().unwrap();
+    assert_eq!(maps.len(), 2);
+    for map in &maps {
+        assert_eq!(map.positions(MapTile::Home).len(), 1);
+        assert_eq!(map.positions(MapTile::Player1).len(), 1);
+        assert!(!map.positions(MapTile::Enemies).is_empty());
+    }
+    // 有渲染时由ldtk插件加载
+    assert!(parse(&["--levels", "levels.ldtk"])
+        .unwrap()
+        .load_level_pack()
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,575 @@
+use std::time::Duration;
+
+use battle_city::{
+    area::*,
+    audio::SoundEvent,
+    bullet::*,
+    camera::*,
+    common::{AppState, Direction, HomeDyingEvent, MultiplayerMode, VersusMode},
+    debug::*,
+    enemy::{spawn_enemy, Enemy, EnemyKind, LevelSpawnedEnemies},
+    event_log::*,
+    level::*,
+    map::*,
+    player::*,
+    sprites::SpriteAssets,
+    survival::*,
+    tile_grid::*,
+};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_ecs_ldtk::LevelSelection;
+use bevy_rapier2d::prelude::*;
+
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// 出生动画2秒后生成玩家坦克，再等待0.5秒的开火间隔
+const SPAWN_TICKS: usize = 180;
+
+// 记录收到的HomeDyingEvent数量
+#[derive(Resource, Default)]
+struct HomeDyingCount(usize);
+
+fn count_home_dying(
+    mut home_dying_er: EventReader<HomeDyingEvent>,
+    mut count: ResMut<HomeDyingCount>,
+) {
+    count.0 += home_dying_er.read().count();
+}
+
+// 不创建窗口和渲染，只运行对局相关系统，每次update固定推进1/60秒
+struct Harness {
+    app: App,
+}
+
+impl Harness {
+    fn new(rows: &[&str]) -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0),
+        ))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlasLayout>()
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_resource::<SpriteAssets>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+        .add_event::<ExplosionEvent>()
+        .add_event::<SpawnPlayerEvent>()
+        .add_event::<HomeDyingEvent>()
+        .add_event::<SoundEvent>()
+        .add_event::<GameEvent>()
+        .init_state::<AppState>()
+        .insert_resource(PlayerLives {
+            player1: 3,
+            player2: 3,
+        })
+        .insert_resource(MultiplayerMode::SinglePlayer)
+        .insert_resource(LevelSpawnedEnemies(0))
+        .init_resource::<PlayerScores>()
+        .init_resource::<PlayerInputs>()
+        .init_resource::<VersusMode>()
+        .init_resource::<GodMode>()
+        .init_resource::<CheatsUsed>()
+        .init_resource::<DevConsole>()
+        .add_event::<ConsoleCommand>()
+        .init_resource::<HomeDyingCount>()
+        .init_resource::<TileGrid>()
+        .init_resource::<LevelBounds>()
+        .init_resource::<SplitScreen>()
+        .init_resource::<SurvivalMode>()
+        .init_resource::<SurvivalWaves>()
+        .init_resource::<GameEventLog>()
+        .init_resource::<GameTick>()
+        .add_systems(
+            PreUpdate,
+            advance_game_tick.run_if(in_state(AppState::Playing)),
+        )
+        .add_systems(PostUpdate, write_game_events)
+        .add_systems(
+            Update,
+            update_survival_waves
+                .after(update_tile_grid)
+                .run_if(survival_enabled),
+        )
+        .add_systems(
+            Update,
+            (
+                auto_spawn_players,
+                players_move,
+                players_attack,
+                animate_born,
+                remove_shield,
+                animate_home,
+                count_home_dying,
+                handle_bullet_collision,
+                move_bullet,
+                update_tile_grid,
+                rebuild_wall,
+                update_split_screen,
+                execute_console_commands,
+            ),
+        );
+        // rapier在Startup中创建物理世界
+        app.finish();
+        app.cleanup();
+        app.update();
+
+        let map = LevelMap::from_text(&rows.join("\n")).unwrap();
+        app.world_mut()
+            .run_system_once(
+                move |mut commands: Commands,
+                      sprite_assets: Res<SpriteAssets>,
+                      mut rapier_config: Single<&mut RapierConfiguration>| {
+                    rapier_config.gravity = Vec2::ZERO;
+                    spawn_level_map(&map, &mut commands, &sprite_assets);
+                },
+            )
+            .unwrap();
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        Harness { app }
+    }
+
+    // 设置玩家1本帧的输入
+    fn input(&mut self, direction: Option<Direction>, fire: bool) {
+        let mut inputs = self.app.world_mut().resource_mut::<PlayerInputs>();
+        inputs.previous = inputs.current;
+        inputs.current[0] = PlayerInput { direction, fire };
+    }
+
+    fn step(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    // 松开所有按键后推进
+    fn idle(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.input(None, false);
+            self.app.update();
+        }
+    }
+
+    fn fire(&mut self) {
+        self.input(None, true);
+        self.step(1);
+    }
+
+    fn spawn_enemy_bullet(&mut self, translation: Vec3, direction: Direction) {
+        self.app
+            .world_mut()
+            .run_system_once(
+                move |mut commands: Commands, sprite_assets: Res<SpriteAssets>| {
+                    spawn_bullet(
+                        &mut commands,
+                        &sprite_assets,
+                        Bullet::Enemy,
+                        translation,
+                        direction,
+                    );
+                },
+            )
+            .unwrap();
+    }
+
+    fn count_level_items(&mut self, item: LevelItem) -> usize {
+        let world = self.app.world_mut();
+        world
+            .query::<&LevelItem>()
+            .iter(world)
+            .filter(|level_item| **level_item == item)
+            .count()
+    }
+
+    fn level_item_translation(&mut self, item: LevelItem) -> Vec3 {
+        let world = self.app.world_mut();
+        world
+            .query::<(&LevelItem, &GlobalTransform)>()
+            .iter(world)
+            .find(|(level_item, _)| **level_item == item)
+            .map(|(_, transform)| transform.translation())
+            .unwrap()
+    }
+
+    fn player(&mut self) -> Option<(Entity, Vec3)> {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<(Entity, &Transform), With<PlayerNo>>()
+            .iter(world)
+            .next()
+            .map(|(entity, transform)| (entity, transform.translation))
+    }
+
+    fn has_shield(&mut self) -> bool {
+        let world = self.app.world_mut();
+        world.query::<&Shield>().iter(world).next().is_some()
+    }
+
+    fn bullets(&mut self) -> usize {
+        let world = self.app.world_mut();
+        world.query::<&Bullet>().iter(world).count()
+    }
+
+    // 格子索引中该位置是否有阻挡物
+    fn wall_in_grid(&self, pos: Vec2) -> bool {
+        self.app
+            .world()
+            .resource::<TileGrid>()
+            .is_blocked(pos - Vec2::ONE, pos + Vec2::ONE)
+    }
+
+    // 场地边界墙的x坐标范围
+    fn area_wall_span(&mut self) -> (f32, f32) {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&Transform, With<AreaWall>>()
+            .iter(world)
+            .fold((f32::MAX, f32::MIN), |(min, max), transform| {
+                (
+                    min.min(transform.translation.x),
+                    max.max(transform.translation.x),
+                )
+            })
+    }
+
+    fn state(&self) -> AppState {
+        self.app.world().resource::<State<AppState>>().get().clone()
+    }
+}
+
+#[test]
+fn player_bullet_destroys_stone_wall() {
+    let mut harness = Harness::new(&[".S.", "...", ".1."]);
+    harness.idle(SPAWN_TICKS);
+    assert!(harness.player().is_some());
+    assert_eq!(harness.count_level_items(LevelItem::StoneWall), 1);
+
+    harness.fire();
+    assert_eq!(harness.bullets(), 1);
+    harness.idle(30);
+    assert_eq!(harness.count_level_items(LevelItem::StoneWall), 0);
+    assert_eq!(harness.bullets(), 0);
+}
+
+#[test]
+fn tile_grid_tracks_destroyed_walls() {
+    let mut harness = Harness::new(&["TS.", "...", ".1."]);
+    harness.idle(SPAWN_TICKS);
+    let wall = harness
+        .level_item_translation(LevelItem::StoneWall)
+        .truncate();
+    let tree = harness.level_item_translation(LevelItem::Tree).truncate();
+    assert!(harness.wall_in_grid(wall));
+    // 树木在格子索引中但不阻挡
+    assert!(!harness.wall_in_grid(tree));
+
+    harness.fire();
+    harness.idle(30);
+    assert_eq!(harness.count_level_items(LevelItem::StoneWall), 0);
+    assert!(!harness.wall_in_grid(wall));
+    assert_eq!(harness.app.world().resource::<TileGrid>().iter().count(), 1);
+}
+
+#[test]
+fn iron_wall_survives_player_bullet() {
+    let mut harness = Harness::new(&[".I.", "...", ".1."]);
+    harness.idle(SPAWN_TICKS);
+    assert!(harness.player().is_some());
+
+    harness.fire();
+    harness.step(1);
+    assert_eq!(harness.bullets(), 1);
+    harness.idle(30);
+    assert_eq!(harness.count_level_items(LevelItem::IronWall), 1);
+    assert_eq!(harness.bullets(), 0);
+}
+
+#[test]
+fn enemy_bullet_on_home_ends_game() {
+    let mut harness = Harness::new(&["...", "...", ".H."]);
+    assert_eq!(harness.state(), AppState::Playing);
+    let home = harness.level_item_translation(LevelItem::Home);
+    harness.spawn_enemy_bullet(home + Vec3::new(0., 64., 0.), Direction::Down);
+    harness.idle(30);
+
+    assert_eq!(harness.app.world().resource::<HomeDyingCount>().0, 1);
+    assert_eq!(harness.state(), AppState::GameOver);
+}
+
+#[test]
+fn shielded_player_survives_hit() {
+    let mut harness = Harness::new(&["...", "...", ".1."]);
+    harness.idle(SPAWN_TICKS);
+    let (player, translation) = harness.player().unwrap();
+    assert!(harness.has_shield());
+
+    harness.spawn_enemy_bullet(translation + Vec3::new(0., 64., 0.), Direction::Down);
+    harness.idle(30);
+    assert_eq!(harness.bullets(), 0);
+    assert_eq!(harness.player().map(|(entity, _)| entity), Some(player));
+    assert_eq!(harness.state(), AppState::Playing);
+}
+
+#[test]
+fn god_mode_player_survives_hit_without_shield() {
+    let mut harness = Harness::new(&["...", "...", ".1."]);
+    harness.idle(SPAWN_TICKS);
+    // 保护盾5秒后消失
+    harness.idle(300);
+    assert!(!harness.has_shield());
+    harness.app.world_mut().resource_mut::<GodMode>().0 = true;
+
+    let (player, translation) = harness.player().unwrap();
+    harness.spawn_enemy_bullet(translation + Vec3::new(0., 64., 0.), Direction::Down);
+    harness.idle(30);
+    assert_eq!(harness.bullets(), 0);
+    assert_eq!(harness.player().map(|(entity, _)| entity), Some(player));
+}
+
+#[test]
+fn area_walls_follow_level_size() {
+    // 比标准关卡更宽的地图
+    let row = ".".repeat(40);
+    let mut harness = Harness::new(&[&row, &row]);
+    harness.step(1);
+    let bounds = *harness.app.world().resource::<LevelBounds>();
+    assert_eq!((bounds.columns, bounds.rows), (40, 2));
+    let half_width = 40. * 32. / 2. + WALL_THICKNESS / 2.;
+    assert_eq!(harness.area_wall_span(), (-half_width, half_width));
+}
+
+#[test]
+fn split_screen_when_players_far_apart() {
+    let mut row = ".".repeat(60);
+    row.replace_range(1..2, "1");
+    row.replace_range(58..59, "2");
+    let mut harness = Harness::new(&[&".".repeat(60), &row]);
+    *harness.app.world_mut().resource_mut::<MultiplayerMode>() = MultiplayerMode::TwoPlayers;
+    harness.idle(SPAWN_TICKS);
+    assert!(harness.app.world().resource::<SplitScreen>().0);
+
+    // 对局结束后合并画面
+    harness
+        .app
+        .world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::GameOver);
+    harness.step(1);
+    assert!(!harness.app.world().resource::<SplitScreen>().0);
+}
+
+#[test]
+fn survival_rebuilds_base_walls_before_first_wave() {
+    let mut harness = Harness::new(&["SSS", "SHS"]);
+    harness.app.world_mut().resource_mut::<SurvivalMode>().0 = true;
+    harness.step(2);
+
+    // 第一波开始前被毁的砖墙逐块修复
+    let wall = harness
+        .app
+        .world_mut()
+        .query::<(Entity, &LevelItem)>()
+        .iter(harness.app.world())
+        .find(|(_, item)| **item == LevelItem::StoneWall)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    harness.app.world_mut().entity_mut(wall).despawn_recursive();
+    harness.step(1);
+    assert_eq!(harness.count_level_items(LevelItem::StoneWall), 4);
+    harness.step(60);
+    assert_eq!(harness.count_level_items(LevelItem::StoneWall), 5);
+    harness.step(240);
+    assert_eq!(harness.app.world().resource::<SurvivalWaves>().wave, 1);
+}
+
+#[test]
+fn event_log_records_shot_hit_and_kill() {
+    let path =
+        std::env::temp_dir().join(format!("battle_city_events_{}.jsonl", std::process::id()));
+    let mut harness = Harness::new(&[".E.", "...", ".1."]);
+    harness
+        .app
+        .insert_resource(GameEventLog::create(path.to_str().unwrap()).unwrap())
+        .insert_resource(LevelSelection::index(0))
+        .insert_resource(CustomLevel(None))
+        .init_resource::<LevelPack>();
+    harness
+        .app
+        .world_mut()
+        .run_system_once(log_stage_start)
+        .unwrap();
+    harness.idle(SPAWN_TICKS);
+    assert!(harness.player().is_some());
+
+    // 敌人出现在玩家正上方，不运行敌人移动和开火系统
+    let marker = {
+        let world = harness.app.world_mut();
+        world
+            .query_filtered::<&GlobalTransform, With<EnemiesMarker>>()
+            .single(world)
+            .translation()
+    };
+    harness
+        .app
+        .world_mut()
+        .run_system_once(
+            move |mut commands: Commands, sprite_assets: Res<SpriteAssets>| {
+                spawn_enemy(marker, EnemyKind::Basic, &mut commands, &sprite_assets);
+            },
+        )
+        .unwrap();
+    harness.fire();
+    harness.idle(30);
+    let world = harness.app.world_mut();
+    assert_eq!(world.query::<&Enemy>().iter(world).count(), 0);
+
+    // 控制台给予的道具也记录
+    harness
+        .app
+        .world_mut()
+        .send_event(ConsoleCommand::Give(PowerUp::Shield));
+    harness
+        .app
+        .world_mut()
+        .send_event(ConsoleCommand::Give(PowerUp::Life));
+    harness.step(1);
+
+    // 暂停时对局帧数不增加
+    let tick = harness.app.world().resource::<GameTick>().0;
+    harness
+        .app
+        .world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Paused);
+    harness.step(1);
+    assert_eq!(harness.state(), AppState::Paused);
+    let paused_tick = harness.app.world().resource::<GameTick>().0;
+    harness.step(10);
+    assert_eq!(harness.app.world().resource::<GameTick>().0, paused_tick);
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let records: Vec<serde_json::Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let events: Vec<&str> = records
+        .iter()
+        .map(|record| record["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "stage_start",
+            "power_up",
+            "shot_fired",
+            "hit",
+            "kill",
+            "power_up",
+            "power_up"
+        ]
+    );
+    assert!(records.iter().all(|record| record["stage"] == 1));
+    let ticks: Vec<u64> = records
+        .iter()
+        .map(|record| record["tick"].as_u64().unwrap())
+        .collect();
+    assert!(ticks.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(ticks[2] > SPAWN_TICKS as u64);
+    assert!(ticks[6] <= tick as u64);
+
+    assert_eq!(records[1]["player"], 1);
+    assert_eq!(records[1]["kind"], "spawn_shield");
+    assert_eq!(records[2]["shooter"], "player1");
+    assert_eq!(records[2]["direction"], "Up");
+    assert_eq!(records[3]["shooter"], "player1");
+    assert_eq!(records[3]["target"], "enemy");
+    assert_eq!(records[4]["killer"], "player1");
+    assert_eq!(records[4]["x"].as_f64(), Some(marker.x as f64));
+    assert_eq!(records[5]["kind"], "shield");
+    assert_eq!(records[6]["kind"], "extra_life");
+}
+
+#[test]
+fn console_commands_mark_cheats_and_skip_level_is_unavailable_in_survival() {
+    let mut harness = Harness::new(&["...", "...", ".1."]);
+    harness.app.world_mut().resource_mut::<SurvivalMode>().0 = true;
+    harness
+        .app
+        .world_mut()
+        .send_event(ConsoleCommand::SkipLevel);
+    harness.app.world_mut().send_event(ConsoleCommand::Help);
+    harness.step(1);
+    let world = harness.app.world();
+    assert!(!world.resource::<CheatsUsed>().0);
+    assert_eq!(world.resource::<LevelSpawnedEnemies>().0, 0);
+    assert_eq!(
+        world
+            .resource::<DevConsole>()
+            .lines
+            .first()
+            .map(String::as_str),
+        Some("skip_level is not available in survival mode")
+    );
+
+    harness.app.world_mut().send_event(ConsoleCommand::God);
+    harness.step(1);
+    assert!(harness.app.world().resource::<CheatsUsed>().0);
+    assert!(harness.app.world().resource::<GodMode>().0);
+}
+
+#[test]
+fn give_shield_skips_born_entities() {
+    let mut harness = Harness::new(&["...", "...", ".1."]);
+    harness.step(1);
+    let world = harness.app.world_mut();
+    assert_eq!(world.query::<&Born>().iter(world).count(), 1);
+    harness
+        .app
+        .world_mut()
+        .send_event(ConsoleCommand::Give(PowerUp::Shield));
+    harness.step(1);
+    assert_eq!(
+        harness
+            .app
+            .world()
+            .resource::<DevConsole>()
+            .lines
+            .last()
+            .map(String::as_str),
+        Some("no player")
+    );
+    assert!(!harness.has_shield());
+
+    // 出生动画结束后坦克自带护盾，给护盾时重置计时
+    harness.idle(SPAWN_TICKS);
+    assert!(harness.player().is_some());
+    harness
+        .app
+        .world_mut()
+        .send_event(ConsoleCommand::Give(PowerUp::Shield));
+    harness.step(1);
+    assert_eq!(
+        harness
+            .app
+            .world()
+            .resource::<DevConsole>()
+            .lines
+            .last()
+            .map(String::as_str),
+        Some("shield given")
+    );
+    assert!(harness.has_shield());
+}
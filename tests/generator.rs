@@ -0,0 +1,73 @@
+use battle_city::{
+    common::{LEVEL_COLUMNS, LEVEL_ROWS},
+    generator::*,
+    map::*,
+};
+
+// 左右镜像后玩家1和玩家2的出生点互换
+fn mirrored(tile: MapTile) -> MapTile {
+    match tile {
+        MapTile::Player1 => MapTile::Player2,
+        MapTile::Player2 => MapTile::Player1,
+        tile => tile,
+    }
+}
+
+#[test]
+fn generated_levels_are_playable_and_symmetric() {
+    for (width, height) in [
+        (LEVEL_COLUMNS, LEVEL_ROWS),
+        (MIN_GENERATED_WIDTH, MIN_GENERATED_HEIGHT),
+        (41, 30),
+    ] {
+        for seed in 0..200 {
+            let map = generate_level(seed, width, height).unwrap();
+            assert_eq!((map.width, map.height), (width, height));
+            assert_eq!(map.positions(MapTile::Home).len(), 1, "seed {}", seed);
+            assert_eq!(map.positions(MapTile::Player1).len(), 1, "seed {}", seed);
+            assert_eq!(map.positions(MapTile::Player2).len(), 1, "seed {}", seed);
+            assert!(!map.positions(MapTile::Enemies).is_empty(), "seed {}", seed);
+            assert!(
+                map.unreachable_spawns().is_empty(),
+                "seed {} {}x{}: unreachable spawns {:?}",
+                seed,
+                width,
+                height,
+                map.unreachable_spawns()
+            );
+            for row in 0..height {
+                for col in 0..width {
+                    assert_eq!(
+                        map.get(width - 1 - col, row),
+                        mirrored(map.get(col, row)),
+                        "seed {} {}x{} at ({}, {})",
+                        seed,
+                        width,
+                        height,
+                        col,
+                        row
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn generate_level_rejects_too_small_sizes() {
+    assert!(generate_level(1, MIN_GENERATED_WIDTH - 1, LEVEL_ROWS).is_err());
+    assert!(generate_level(1, LEVEL_COLUMNS, MIN_GENERATED_HEIGHT - 1).is_err());
+    assert!(generate_level(1, LEVEL_COLUMNS, 3).is_err());
+    assert!(generate_level(1, 0, 0).is_err());
+}
+
+#[test]
+fn generate_level_rejects_even_widths() {
+    for width in [MIN_GENERATED_WIDTH + 1, LEVEL_COLUMNS + 1, 40] {
+        assert!(
+            generate_level(1, width, LEVEL_ROWS).is_err(),
+            "width {}",
+            width
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use battle_city::high_score::*;
+
+fn entry(initials: &str, score: u32) -> HighScoreEntry {
+    HighScoreEntry {
+        initials: initials.to_string(),
+        score,
+        stage: 1,
+        mode: "1P".to_string(),
+        difficulty: "NORMAL".to_string(),
+        survival_secs: None,
+    }
+}
+
+fn survival_entry(initials: &str, wave: usize, secs: u32) -> HighScoreEntry {
+    HighScoreEntry {
+        stage: wave,
+        survival_secs: Some(secs),
+        ..entry(initials, 0)
+    }
+}
+
+fn initials(high_scores: &HighScores) -> Vec<&str> {
+    high_scores
+        .entries
+        .iter()
+        .map(|entry| entry.initials.as_str())
+        .collect()
+}
+
+#[test]
+fn insert_keeps_scores_sorted_and_ties_in_order() {
+    let mut high_scores = HighScores::default();
+    assert_eq!(high_scores.insert(entry("AAA", 500)), 0);
+    assert_eq!(high_scores.insert(entry("BBB", 900)), 0);
+    assert_eq!(high_scores.insert(entry("CCC", 700)), 1);
+    // 同分时先上榜的排在前面
+    assert_eq!(high_scores.insert(entry("DDD", 700)), 2);
+    assert_eq!(high_scores.insert(entry("EEE", 100)), 4);
+    assert_eq!(initials(&high_scores), ["BBB", "CCC", "DDD", "AAA", "EEE"]);
+}
+
+#[test]
+fn board_is_capped_at_max_entries() {
+    let mut high_scores = HighScores::default();
+    for score in 1..=MAX_HIGH_SCORES as u32 {
+        let candidate = entry("AAA", score * 100);
+        assert!(high_scores.qualifies(&candidate));
+        high_scores.insert(candidate);
+    }
+    assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+
+    // 榜满后必须高于最后一名才能上榜
+    assert!(!high_scores.qualifies(&entry("LOW", 50)));
+    assert!(!high_scores.qualifies(&entry("TIE", 100)));
+    assert!(high_scores.qualifies(&entry("NEW", 150)));
+
+    assert_eq!(high_scores.insert(entry("NEW", 150)), MAX_HIGH_SCORES - 1);
+    assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+    assert_eq!(high_scores.entries.first().unwrap().score, 1000);
+    assert_eq!(high_scores.entries.last().unwrap().initials, "NEW");
+    assert!(high_scores.entries.iter().all(|entry| entry.score != 100));
+}
+
+#[test]
+fn zero_score_does_not_qualify() {
+    let high_scores = HighScores::default();
+    assert!(!high_scores.qualifies(&entry("AAA", 0)));
+    assert!(!high_scores.qualifies(&survival_entry("AAA", 0, 0)));
+}
+
+#[test]
+fn survival_ranks_by_wave_then_time() {
+    let mut high_scores = HighScores::default();
+    high_scores.insert(survival_entry("AAA", 3, 200));
+    high_scores.insert(survival_entry("BBB", 5, 100));
+    high_scores.insert(survival_entry("CCC", 3, 250));
+    assert_eq!(initials(&high_scores), ["BBB", "CCC", "AAA"]);
+}
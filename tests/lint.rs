@@ -0,0 +1,125 @@
+use battle_city::{lint::*, map::*};
+use bevy::prelude::*;
+
+// 7x5的最小合法关卡：顶部三个敌人出生点，底部家、砖墙和两个玩家出生点
+fn base_level() -> LintLevel {
+    LintLevel::from_map("test", &LevelMap::with_base(7, 5))
+}
+
+fn has_issue(issues: &[LintIssue], severity: LintSeverity, message: &str) -> bool {
+    issues
+        .iter()
+        .any(|issue| issue.severity == severity && issue.message.contains(message))
+}
+
+fn remove_tile(level: &mut LintLevel, tile: MapTile) {
+    level.placements.retain(|(t, _)| *t != tile);
+}
+
+#[test]
+fn base_level_has_no_issues() {
+    let issues = lint_level(&base_level());
+    assert!(issues.is_empty(), "{:?}", issues);
+}
+
+#[test]
+fn missing_and_duplicate_home() {
+    let mut level = base_level();
+    remove_tile(&mut level, MapTile::Home);
+    let issues = lint_level(&level);
+    assert!(has_issue(&issues, LintSeverity::Error, "missing Home"));
+
+    let mut level = base_level();
+    level.placements.push((MapTile::Home, IVec2::new(3, 2)));
+    let issues = lint_level(&level);
+    assert!(has_issue(&issues, LintSeverity::Error, "2 duplicate Home"));
+}
+
+#[test]
+fn missing_and_duplicate_player_markers() {
+    let mut level = base_level();
+    remove_tile(&mut level, MapTile::Player1);
+    remove_tile(&mut level, MapTile::Player2);
+    let issues = lint_level(&level);
+    assert!(has_issue(&issues, LintSeverity::Error, "missing Player1"));
+    // 缺少2P出生点只影响双人模式
+    assert!(has_issue(&issues, LintSeverity::Warning, "missing Player2"));
+
+    let mut level = base_level();
+    level.placements.push((MapTile::Player1, IVec2::new(1, 2)));
+    level.placements.push((MapTile::Player2, IVec2::new(5, 2)));
+    let issues = lint_level(&level);
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Error,
+        "2 duplicate Player1"
+    ));
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Error,
+        "2 duplicate Player2"
+    ));
+
+    let mut level = base_level();
+    remove_tile(&mut level, MapTile::Enemies);
+    let issues = lint_level(&level);
+    assert!(has_issue(&issues, LintSeverity::Error, "missing Enemies"));
+}
+
+#[test]
+fn marker_on_solid_tile() {
+    let mut level = base_level();
+    level.placements.push((MapTile::IronWall, IVec2::new(1, 4)));
+    let issues = lint_level(&level);
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Error,
+        "Player1 marker at (1, 4) overlaps solid IronWall"
+    ));
+}
+
+#[test]
+fn unreachable_base() {
+    let mut level = base_level();
+    for col in 0..7 {
+        level.placements.push((MapTile::Water, IVec2::new(col, 2)));
+    }
+    let issues = lint_level(&level);
+    for col in [0, 3, 6] {
+        assert!(has_issue(
+            &issues,
+            LintSeverity::Error,
+            &format!("Enemies at ({}, 0) cannot reach the Home", col)
+        ));
+    }
+    // 玩家在水面下方，仍然可以到达家
+    assert!(!has_issue(&issues, LintSeverity::Error, "Player1 at"));
+}
+
+#[test]
+fn out_of_bounds_and_unknown_entities() {
+    let mut level = base_level();
+    level
+        .placements
+        .push((MapTile::StoneWall, IVec2::new(7, 0)));
+    level.placements.push((MapTile::Tree, IVec2::new(0, -1)));
+    level
+        .unknown_entities
+        .push(("Bonus".to_string(), IVec2::new(2, 2)));
+    let issues = lint_level(&level);
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Error,
+        "StoneWall at (7, 0) is outside the 7x5 grid"
+    ));
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Error,
+        "Tree at (0, -1) is outside the 7x5 grid"
+    ));
+    assert!(has_issue(
+        &issues,
+        LintSeverity::Warning,
+        "unknown entity Bonus at (2, 2)"
+    ));
+}
@@ -0,0 +1,18 @@
+use battle_city::{common::Direction, minimap::home_under_threat};
+use bevy::prelude::*;
+
+#[test]
+fn home_threat_from_nearby_enemy_or_incoming_bullet() {
+    let home = Vec2::new(0., -256.);
+    assert!(!home_under_threat(home, &[Vec2::new(0., 256.)], &[]));
+    assert!(home_under_threat(home, &[Vec2::new(64., -192.)], &[]));
+
+    let bullet = Vec2::new(10., 200.);
+    assert!(home_under_threat(home, &[], &[(bullet, Direction::Down)]));
+    assert!(!home_under_threat(home, &[], &[(bullet, Direction::Up)]));
+    assert!(!home_under_threat(
+        home,
+        &[],
+        &[(bullet + Vec2::new(64., 0.), Direction::Down)]
+    ));
+}
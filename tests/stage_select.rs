@@ -0,0 +1,26 @@
+use battle_city::{
+    common::{LEVEL_COLUMNS, LEVEL_ROWS},
+    map::LevelMap,
+    stage_select::*,
+};
+use bevy::prelude::*;
+
+#[test]
+fn thumbnail_keeps_level_aspect_ratio() {
+    let frame = thumbnail_size(&LevelMap::new(LEVEL_COLUMNS, LEVEL_ROWS));
+    // 小关卡不放大
+    let small = thumbnail_size(&LevelMap::new(10, 5));
+    assert_eq!(
+        small,
+        frame * Vec2::new(10., 5.) / Vec2::new(LEVEL_COLUMNS as f32, LEVEL_ROWS as f32)
+    );
+    // 大关卡缩小到框内
+    for (width, height) in [
+        (LEVEL_COLUMNS * 3, LEVEL_ROWS),
+        (LEVEL_COLUMNS, LEVEL_ROWS * 4),
+    ] {
+        let size = thumbnail_size(&LevelMap::new(width, height));
+        assert!(size.x <= frame.x && size.y <= frame.y);
+        assert!((size.x / size.y - width as f32 / height as f32).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,9 @@
+use battle_city::{enemy::EnemyKind, survival::*};
+
+#[test]
+fn waves_escalate() {
+    assert!(wave_size(5) > wave_size(1));
+    assert!(wave_spawn_interval(5) < wave_spawn_interval(1));
+    assert_eq!(wave_enemy_kind(1, 0.), EnemyKind::Basic);
+    assert_eq!(wave_enemy_kind(8, 0.), EnemyKind::Armor);
+}
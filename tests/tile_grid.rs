@@ -0,0 +1,53 @@
+use battle_city::{common::TILE_SIZE, level::LevelItem, tile_grid::*};
+use bevy::prelude::*;
+
+// 在(0, 0)处放一个地图元素
+fn grid_with(item: LevelItem) -> TileGrid {
+    let mut tile_grid = TileGrid::default();
+    tile_grid.insert(Entity::from_raw(1), item, Vec2::ZERO);
+    tile_grid
+}
+
+#[test]
+fn tree_is_passable() {
+    let tile_grid = grid_with(LevelItem::Tree);
+    assert!(!tile_grid.is_blocked(Vec2::splat(-TILE_SIZE), Vec2::splat(TILE_SIZE)));
+    assert_eq!(
+        tile_grid
+            .items_in(Vec2::splat(-TILE_SIZE), Vec2::splat(TILE_SIZE))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn walls_and_water_block() {
+    for item in [LevelItem::StoneWall, LevelItem::IronWall, LevelItem::Water] {
+        let tile_grid = grid_with(item.clone());
+        assert!(
+            tile_grid.is_blocked(Vec2::splat(-TILE_SIZE), Vec2::splat(TILE_SIZE)),
+            "{:?}",
+            item
+        );
+    }
+}
+
+#[test]
+fn bounds_are_strict() {
+    let tile_grid = grid_with(LevelItem::StoneWall);
+    // 中心点正好在边界上时不算在矩形内
+    assert!(!tile_grid.is_blocked(Vec2::ZERO, Vec2::splat(TILE_SIZE)));
+    assert!(!tile_grid.is_blocked(Vec2::splat(-TILE_SIZE), Vec2::ZERO));
+    assert!(!tile_grid.is_blocked(Vec2::new(0., -TILE_SIZE), Vec2::new(TILE_SIZE, TILE_SIZE)));
+    assert!(tile_grid.is_blocked(Vec2::splat(-0.1), Vec2::splat(0.1)));
+    // 远处的格子
+    assert!(!tile_grid.is_blocked(Vec2::splat(TILE_SIZE), Vec2::splat(3. * TILE_SIZE)));
+}
+
+#[test]
+fn removed_items_no_longer_block() {
+    let mut tile_grid = grid_with(LevelItem::StoneWall);
+    tile_grid.remove(Entity::from_raw(1));
+    assert!(!tile_grid.is_blocked(Vec2::splat(-TILE_SIZE), Vec2::splat(TILE_SIZE)));
+    assert_eq!(tile_grid.iter().count(), 0);
+}